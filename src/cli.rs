@@ -1,7 +1,15 @@
-use clap::{Args, Parser, Subcommand};
+use std::{collections::BTreeMap, path::PathBuf};
 
-use crate::config::{FlagConfig, ModelConfig, NetworkConfig, Preset, TimeoutConfig};
-use crate::provider::ProviderKind;
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+
+use crate::bundle::ConflictPolicy;
+use crate::clock;
+use crate::config::{self, FlagConfig, ModelConfig, NetworkConfig, Preset, TimeoutConfig};
+use crate::errors::{AppError, Result};
+use crate::paths::Scope;
+use crate::provider::{ProviderKind, ProviderTemplate};
+use crate::settings::OVERRIDE_ENV_KEYS;
+use crate::shell::{HookShell, Shell};
 
 #[derive(Debug, Parser)]
 #[command(
@@ -10,10 +18,34 @@ use crate::provider::ProviderKind;
     about = "Manage Claude Code model presets and switch provider config"
 )]
 pub struct Cli {
+    /// Output format for command results.
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+    /// Shorthand for `--format json`.
+    #[arg(long, global = true)]
+    pub json: bool,
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
 
+impl Cli {
+    pub fn output_format(&self) -> OutputFormat {
+        if self.json {
+            OutputFormat::Json
+        } else {
+            self.format
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+#[value(rename_all = "lower")]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
 #[derive(Debug, Subcommand)]
 pub enum Commands {
     /// List all presets.
@@ -24,44 +56,228 @@ pub enum Commands {
     Use {
         /// Preset name to activate.
         preset: String,
+        /// Print the env changes without writing settings.json.
+        #[arg(long)]
+        dry_run: bool,
+        /// Settings file to apply the preset to.
+        #[arg(long, value_enum, default_value_t = Scope::User)]
+        scope: Scope,
+        /// Run the `test` connectivity check first and only switch if it passes.
+        #[arg(long)]
+        verify: bool,
+        /// Allow `--scope project` to write the auth token into the usually committed settings.json.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Send a minimal request per tier model to a preset's endpoint and report the results.
+    Test {
+        /// Preset name to check.
+        preset: String,
+    },
+    /// Run a command with a preset's env without changing settings.json.
+    Exec {
+        /// Preset whose env is passed to the command.
+        preset: String,
+        /// Command to run, after `--` (typically `claude`).
+        #[arg(last = true, required = true, value_name = "COMMAND")]
+        command: Vec<String>,
+    },
+    /// Print shell lines that export a preset's env, for `eval` in CI or containers.
+    Env {
+        /// Preset whose env is printed.
+        #[arg(required_unless_present = "unset")]
+        preset: Option<String>,
+        /// Syntax of the printed lines.
+        #[arg(long, value_enum, default_value_t = Shell::Bash)]
+        shell: Shell,
+        /// Print lines that unset every managed key (and the preset's extra env) instead.
+        /// Dotenv has no unset syntax, so nothing is printed for it.
+        #[arg(long)]
+        unset: bool,
+    },
+    /// List the built-in providers and the defaults `add --provider` fills in.
+    Providers,
+    /// Apply the preset chosen by the nearest `.ccswitcher` file in this or a parent directory.
+    Auto {
+        /// Settings file to apply to (default: the marker's `scope`, else user).
+        #[arg(long, value_enum)]
+        scope: Option<Scope>,
+        /// Only print when settings change, for use from shell hooks.
+        #[arg(long)]
+        quiet: bool,
+        /// Allow a project scope to write the auth token into the usually committed settings.json.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Approve a `.ccswitcher` file as it is now, so `auto` may apply it.
+    Allow {
+        /// Marker file or its directory (default: the nearest marker from here).
+        path: Option<PathBuf>,
+    },
+    /// Withdraw the approval of a `.ccswitcher` file.
+    Deny {
+        /// Marker file or its directory (default: the nearest marker from here).
+        path: Option<PathBuf>,
+    },
+    /// Print a shell hook that runs `ccswitcher auto` after every directory change.
+    Hook {
+        /// Shell to generate the hook for.
+        #[arg(value_enum)]
+        shell: HookShell,
+    },
+    /// Show how a preset's env differs from the live settings.json.
+    Diff {
+        /// Preset name to compare against.
+        preset: String,
     },
     /// Add a preset.
-    Add(AddArgs),
+    Add(AddCommand),
+    /// Update fields of an existing preset in place.
+    Edit(EditArgs),
+    /// Rename a preset, keeping it active in every scope where it was active.
+    ///
+    /// Refused while an approved `.ccswitcher` marker still names the preset.
+    Rename {
+        /// Current preset name.
+        old: String,
+        /// New preset name.
+        #[arg(value_parser = parse_preset_name)]
+        new: String,
+    },
+    /// Copy a preset under a new name, optionally overriding fields on the copy.
+    Clone(CloneArgs),
     /// Remove a preset.
     Remove {
         /// Preset name to remove.
         preset: String,
     },
+    /// Export presets to a portable bundle file.
+    Export(ExportArgs),
+    /// Import presets from a bundle file.
+    Import(ImportArgs),
+    /// Show the log of use/reset-official/add/remove operations.
+    History {
+        /// Only show entries newer than an age (30m, 12h, 7d, 2w) or a UTC date (YYYY-MM-DD).
+        #[arg(long, value_parser = parse_since)]
+        since: Option<u64>,
+    },
+    /// Reconcile the active preset with settings.json after external edits.
+    Sync(SyncArgs),
+    /// Revert the last switch (use, reset-official, or edit of the active preset).
+    Undo,
+    /// Re-apply the last undone switch.
+    Redo,
     /// Clear model/provider env overrides and return to Claude official defaults.
     #[command(name = "reset-official", visible_alias = "reset")]
-    ResetOfficial,
+    ResetOfficial {
+        /// Print the env changes without writing settings.json.
+        #[arg(long)]
+        dry_run: bool,
+        /// Settings file to reset.
+        #[arg(long, value_enum, default_value_t = Scope::User)]
+        scope: Scope,
+    },
     /// Install /switchmodel slash command template into ~/.claude/commands.
     Install,
+    /// List, restore and prune settings.json backups.
+    Backups {
+        #[command(subcommand)]
+        command: BackupCommands,
+    },
+    /// Check ccswitcher and Claude Code files for common problems.
+    Doctor,
+    /// Manage the encrypted auth token vault.
+    Vault {
+        #[command(subcommand)]
+        command: VaultCommands,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum VaultCommands {
+    /// Create the vault, protected by a passphrase (prompted or CCSWITCHER_PASSPHRASE).
+    Init,
+    /// Forget the cached vault key so the passphrase is asked for again.
+    Lock,
+    /// Move plain-text auth tokens from config.json into the vault.
+    Migrate,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum BackupCommands {
+    /// List settings.json backups, newest first.
+    List,
+    /// Restore settings.json from a backup.
+    Restore {
+        /// Backup id from `backups list`, or `latest`.
+        backup: String,
+    },
+    /// Delete all but the newest N backups.
+    Prune {
+        /// Number of backups to keep.
+        #[arg(long)]
+        keep: usize,
+    },
+    /// Show or set how many backups are kept automatically (0 keeps all).
+    Retention {
+        /// New retention count.
+        keep: Option<usize>,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct AddCommand {
+    /// Save the env currently in settings.json as a new preset with this name.
+    #[arg(
+        long,
+        value_name = "NAME",
+        group = "token_source",
+        conflicts_with = "AddArgs",
+        value_parser = parse_preset_name
+    )]
+    pub from_current: Option<String>,
+    #[command(flatten)]
+    pub preset: Option<AddArgs>,
 }
 
 #[derive(Debug, Args)]
+#[command(group(ArgGroup::new("token_source").required(true).multiple(false)))]
 pub struct AddArgs {
     /// Unique preset name.
-    #[arg(long)]
+    #[arg(
+        long,
+        required = false,
+        required_unless_present = "from_current",
+        value_parser = parse_preset_name
+    )]
     pub name: String,
-    /// Provider type.
-    #[arg(long, value_enum, default_value_t = ProviderKind::Custom)]
+    /// Provider from `ccswitcher providers`; fills in omitted URL, models and extra env.
+    #[arg(long, default_value_t = ProviderKind::Custom)]
     pub provider: ProviderKind,
-    /// Anthropic-compatible base URL.
+    /// Anthropic-compatible base URL (default: the provider's).
     #[arg(long)]
-    pub base_url: String,
-    /// Provider auth token.
+    pub base_url: Option<String>,
+    /// Provider auth token (stored in plain text).
+    #[arg(long, group = "token_source")]
+    pub auth_token: Option<String>,
+    /// Read the auth token from this environment variable at `use` time.
+    #[arg(long, value_name = "VAR", group = "token_source")]
+    pub auth_token_env: Option<String>,
+    /// Read the auth token from this file at `use` time.
+    #[arg(long, value_name = "PATH", group = "token_source")]
+    pub auth_token_file: Option<String>,
+    /// Run this shell command at `use` time and use its output as the auth token.
+    #[arg(long, value_name = "COMMAND", group = "token_source")]
+    pub auth_token_cmd: Option<String>,
+    /// Default model for ANTHROPIC_DEFAULT_HAIKU_MODEL (default: the provider's).
     #[arg(long)]
-    pub auth_token: String,
-    /// Default model for ANTHROPIC_DEFAULT_HAIKU_MODEL.
+    pub haiku: Option<String>,
+    /// Default model for ANTHROPIC_DEFAULT_SONNET_MODEL (default: the provider's).
     #[arg(long)]
-    pub haiku: String,
-    /// Default model for ANTHROPIC_DEFAULT_SONNET_MODEL.
-    #[arg(long)]
-    pub sonnet: String,
-    /// Default model for ANTHROPIC_DEFAULT_OPUS_MODEL.
+    pub sonnet: Option<String>,
+    /// Default model for ANTHROPIC_DEFAULT_OPUS_MODEL (default: the provider's).
     #[arg(long)]
-    pub opus: String,
+    pub opus: Option<String>,
     /// Optional HTTP proxy URL.
     #[arg(long)]
     pub http_proxy: Option<String>,
@@ -74,23 +290,67 @@ pub struct AddArgs {
     /// Optional flag for CLAUDE_CODE_DISABLE_NONESSENTIAL_TRAFFIC.
     #[arg(long)]
     pub disable_nonessential_traffic: Option<bool>,
+    /// Extra env var written alongside the preset (repeatable).
+    #[arg(long = "env", value_name = "KEY=VALUE", value_parser = parse_env_pair)]
+    pub env: Vec<(String, String)>,
 }
 
 impl AddArgs {
-    pub fn into_name_and_preset(self) -> (String, Preset) {
+    /// The `auth_token` config value: a plain token or an `env:`/`file:`/`cmd:` reference.
+    pub fn auth_token_value(&self) -> String {
+        let sources = [
+            ("", &self.auth_token),
+            ("env:", &self.auth_token_env),
+            ("file:", &self.auth_token_file),
+            ("cmd:", &self.auth_token_cmd),
+        ];
+        sources
+            .into_iter()
+            .find_map(|(prefix, value)| {
+                value
+                    .as_deref()
+                    .map(|value| format!("{prefix}{}", value.trim()))
+            })
+            .unwrap_or_default()
+    }
+
+    /// Builds the preset, filling omitted fields from the provider `template`.
+    pub fn into_name_and_preset(
+        self,
+        template: Option<&ProviderTemplate>,
+    ) -> Result<(String, Preset)> {
+        if let Some(field) = template.and_then(|t| self.missing_required(t)) {
+            return Err(AppError::ProviderFieldRequired {
+                provider: self.provider.to_string(),
+                field,
+            });
+        }
+
         let name = self.name.trim().to_owned();
         let disable_nonessential_traffic = self
             .disable_nonessential_traffic
             .map(|value| value.to_string());
 
+        let auth_token = self.auth_token_value();
+        let or_default =
+            |value: Option<String>, default: fn(&ProviderTemplate) -> &Option<String>| {
+                value
+                    .or_else(|| template.and_then(|t| default(t).clone()))
+                    .map(|value| value.trim().to_owned())
+                    .unwrap_or_default()
+            };
+        let mut extra_env: BTreeMap<String, String> =
+            template.map(|t| t.extra_env.clone()).unwrap_or_default();
+        extra_env.extend(self.env);
+
         let preset = Preset {
             provider: self.provider,
-            base_url: self.base_url.trim().to_owned(),
-            auth_token: self.auth_token.trim().to_owned(),
+            base_url: or_default(self.base_url, |t| &t.base_url),
+            auth_token,
             models: ModelConfig {
-                haiku_model: self.haiku.trim().to_owned(),
-                sonnet_model: self.sonnet.trim().to_owned(),
-                opus_model: self.opus.trim().to_owned(),
+                haiku_model: or_default(self.haiku, |t| &t.haiku_model),
+                sonnet_model: or_default(self.sonnet, |t| &t.sonnet_model),
+                opus_model: or_default(self.opus, |t| &t.opus_model),
             },
             network: self.http_proxy.map(|http_proxy| NetworkConfig {
                 http_proxy: Some(http_proxy.trim().to_owned()),
@@ -103,8 +363,240 @@ impl AddArgs {
             flags: disable_nonessential_traffic.map(|value| FlagConfig {
                 disable_nonessential_traffic: Some(value),
             }),
+            extra_env,
         };
 
-        (name, preset)
+        Ok((name, preset))
+    }
+
+    fn missing_required(&self, template: &ProviderTemplate) -> Option<String> {
+        template
+            .required
+            .iter()
+            .find(|field| {
+                let given = match field.as_str() {
+                    "base_url" => self.base_url.is_some(),
+                    "haiku" => self.haiku.is_some(),
+                    "sonnet" => self.sonnet.is_some(),
+                    "opus" => self.opus.is_some(),
+                    key => self.env.iter().any(|(set, _)| set == key),
+                };
+                !given
+            })
+            .cloned()
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    /// Preset names to export (default: all presets).
+    pub presets: Vec<String>,
+    /// Bundle file to write.
+    #[arg(long)]
+    pub out: PathBuf,
+    /// Keep auth tokens in the bundle instead of stripping them.
+    #[arg(long)]
+    pub include_tokens: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ImportArgs {
+    /// Bundle file to read.
+    pub file: PathBuf,
+    /// What to do when an imported preset name already exists.
+    #[arg(long, value_enum, default_value_t = ConflictPolicy::Skip)]
+    pub on_conflict: ConflictPolicy,
+    /// Accept presets whose auth token runs a command (`cmd:`) or reads a file (`file:`).
+    #[arg(long)]
+    pub allow_token_commands: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct EditArgs {
+    /// Preset name to edit.
+    pub preset: String,
+    #[command(flatten)]
+    pub overrides: PresetOverrides,
+}
+
+#[derive(Debug, Args)]
+#[command(group(ArgGroup::new("direction").required(true).multiple(false)))]
+pub struct SyncArgs {
+    /// Overwrite the drifted settings.json keys with the preset's values.
+    #[arg(long, group = "direction")]
+    pub reapply: bool,
+    /// Copy the live settings.json values back into the preset.
+    #[arg(long, group = "direction")]
+    pub capture: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct CloneArgs {
+    /// Preset name to copy from.
+    pub source: String,
+    /// Name of the new preset.
+    #[arg(value_parser = parse_preset_name)]
+    pub target: String,
+    #[command(flatten)]
+    pub overrides: PresetOverrides,
+}
+
+/// Optional preset fields shared by `edit` and `clone`; unset fields keep their stored value.
+#[derive(Debug, Default, Args)]
+pub struct PresetOverrides {
+    /// Provider from `ccswitcher providers` (only the tag changes; fields are kept).
+    #[arg(long)]
+    pub provider: Option<ProviderKind>,
+    /// Anthropic-compatible base URL.
+    #[arg(long)]
+    pub base_url: Option<String>,
+    /// Provider auth token.
+    #[arg(long)]
+    pub auth_token: Option<String>,
+    /// Default model for ANTHROPIC_DEFAULT_HAIKU_MODEL.
+    #[arg(long)]
+    pub haiku: Option<String>,
+    /// Default model for ANTHROPIC_DEFAULT_SONNET_MODEL.
+    #[arg(long)]
+    pub sonnet: Option<String>,
+    /// Default model for ANTHROPIC_DEFAULT_OPUS_MODEL.
+    #[arg(long)]
+    pub opus: Option<String>,
+    /// Optional HTTP proxy URL (empty string clears it).
+    #[arg(long)]
+    pub http_proxy: Option<String>,
+    /// Optional API timeout in milliseconds (empty string clears it).
+    #[arg(long)]
+    pub api_timeout_ms: Option<String>,
+    /// Optional MCP tool timeout in milliseconds (empty string clears it).
+    #[arg(long)]
+    pub mcp_tool_timeout: Option<String>,
+    /// Optional flag for CLAUDE_CODE_DISABLE_NONESSENTIAL_TRAFFIC.
+    #[arg(long)]
+    pub disable_nonessential_traffic: Option<bool>,
+    /// Remove the CLAUDE_CODE_DISABLE_NONESSENTIAL_TRAFFIC flag.
+    #[arg(long, conflicts_with = "disable_nonessential_traffic")]
+    pub unset_disable_nonessential_traffic: bool,
+    /// Extra env var to add or replace (repeatable).
+    #[arg(long = "env", value_name = "KEY=VALUE", value_parser = parse_env_pair)]
+    pub env: Vec<(String, String)>,
+    /// Extra env var to remove (repeatable).
+    #[arg(long = "unset-env", value_name = "KEY")]
+    pub unset_env: Vec<String>,
+}
+
+impl PresetOverrides {
+    pub fn has_changes(&self) -> bool {
+        self.provider.is_some()
+            || self.base_url.is_some()
+            || self.auth_token.is_some()
+            || self.haiku.is_some()
+            || self.sonnet.is_some()
+            || self.opus.is_some()
+            || self.http_proxy.is_some()
+            || self.api_timeout_ms.is_some()
+            || self.mcp_tool_timeout.is_some()
+            || self.disable_nonessential_traffic.is_some()
+            || self.unset_disable_nonessential_traffic
+            || !self.env.is_empty()
+            || !self.unset_env.is_empty()
+    }
+
+    /// Merges the provided fields into `preset`, leaving unspecified fields untouched.
+    pub fn apply_to(self, preset: &mut Preset) {
+        if let Some(provider) = self.provider {
+            preset.provider = provider;
+        }
+        if let Some(base_url) = self.base_url {
+            preset.base_url = base_url.trim().to_owned();
+        }
+        if let Some(auth_token) = self.auth_token {
+            preset.auth_token = auth_token.trim().to_owned();
+        }
+        if let Some(haiku) = self.haiku {
+            preset.models.haiku_model = haiku.trim().to_owned();
+        }
+        if let Some(sonnet) = self.sonnet {
+            preset.models.sonnet_model = sonnet.trim().to_owned();
+        }
+        if let Some(opus) = self.opus {
+            preset.models.opus_model = opus.trim().to_owned();
+        }
+
+        if let Some(http_proxy) = self.http_proxy {
+            preset.network = non_empty(http_proxy).map(|http_proxy| NetworkConfig {
+                http_proxy: Some(http_proxy),
+            });
+        }
+
+        if self.api_timeout_ms.is_some() || self.mcp_tool_timeout.is_some() {
+            let mut timeouts = preset.timeouts.take().unwrap_or(TimeoutConfig {
+                api_timeout_ms: None,
+                mcp_tool_timeout: None,
+            });
+            if let Some(api_timeout_ms) = self.api_timeout_ms {
+                timeouts.api_timeout_ms = non_empty(api_timeout_ms);
+            }
+            if let Some(mcp_tool_timeout) = self.mcp_tool_timeout {
+                timeouts.mcp_tool_timeout = non_empty(mcp_tool_timeout);
+            }
+            preset.timeouts = Some(timeouts)
+                .filter(|t| t.api_timeout_ms.is_some() || t.mcp_tool_timeout.is_some());
+        }
+
+        if let Some(value) = self.disable_nonessential_traffic {
+            preset.flags = Some(FlagConfig {
+                disable_nonessential_traffic: Some(value.to_string()),
+            });
+        } else if self.unset_disable_nonessential_traffic {
+            preset.flags = None;
+        }
+
+        for key in self.unset_env {
+            preset.extra_env.remove(key.trim());
+        }
+        preset.extra_env.extend(self.env);
+    }
+}
+
+fn parse_since(raw: &str) -> std::result::Result<u64, String> {
+    clock::parse_since(raw, clock::now_millis())
+        .ok_or_else(|| format!("expected an age like 7d or a date like 2026-01-31, got '{raw}'"))
+}
+
+/// Trims a new preset name, rejecting blank ones.
+fn parse_preset_name(raw: &str) -> std::result::Result<String, String> {
+    let name = raw.trim();
+    if name.is_empty() {
+        return Err("preset name must not be empty".to_owned());
+    }
+    Ok(name.to_owned())
+}
+
+/// Parses `KEY=VALUE`, rejecting keys already covered by dedicated preset options.
+pub fn parse_env_pair(raw: &str) -> std::result::Result<(String, String), String> {
+    let (key, value) = raw
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{raw}'"))?;
+    let key = key.trim();
+    if !config::is_env_key(key) {
+        return Err(format!(
+            "invalid env key '{key}': use letters, digits and '_', not starting with a digit"
+        ));
+    }
+    if OVERRIDE_ENV_KEYS.contains(&key) {
+        return Err(format!(
+            "'{key}' is managed by a dedicated preset option; set it there instead"
+        ));
+    }
+    Ok((key.to_owned(), value.trim().to_owned()))
+}
+
+fn non_empty(value: String) -> Option<String> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        None
+    } else {
+        Some(trimmed.to_owned())
     }
 }
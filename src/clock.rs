@@ -0,0 +1,96 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+/// Formats a Unix timestamp in milliseconds as `YYYY-MM-DD HH:MM:SS` (UTC).
+pub fn format_utc(millis: u64) -> String {
+    let secs = millis / 1000;
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    let (year, month, day) = civil_from_days(days as i64);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+/// Parses a `--since` value: a relative age such as `30m`, `12h`, `7d`, or a
+/// UTC date `YYYY-MM-DD`. Returns the cutoff as Unix milliseconds.
+pub fn parse_since(raw: &str, now_ms: u64) -> Option<u64> {
+    let raw = raw.trim();
+    if let Some(unit) = raw.chars().last().filter(|c| c.is_ascii_alphabetic()) {
+        let amount: u64 = raw[..raw.len() - 1].parse().ok()?;
+        let unit_ms = match unit {
+            'm' => 60_000,
+            'h' => 3_600_000,
+            'd' => 86_400_000,
+            'w' => 7 * 86_400_000,
+            _ => return None,
+        };
+        return Some(now_ms.saturating_sub(amount.checked_mul(unit_ms)?));
+    }
+
+    let mut parts = raw.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: u32 = parts.next()?.parse().ok()?;
+    let day: u32 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let days = days_from_civil(year, month, day);
+    u64::try_from(days).ok().map(|days| days * 86_400_000)
+}
+
+// Howard Hinnant's civil-to-days conversion.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let month = i64::from(month);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+// Howard Hinnant's days-to-civil conversion.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_utc_renders_calendar_dates() {
+        assert_eq!(format_utc(0), "1970-01-01 00:00:00");
+        assert_eq!(format_utc(951_827_696_000), "2000-02-29 12:34:56");
+        assert_eq!(format_utc(1_792_195_200_000), "2026-10-17 00:00:00");
+    }
+
+    #[test]
+    fn parse_since_accepts_relative_ages_and_dates() {
+        let now = 1_792_195_200_000;
+        assert_eq!(parse_since("2h", now), Some(now - 2 * 3_600_000));
+        assert_eq!(parse_since("7d", now), Some(now - 7 * 86_400_000));
+        assert_eq!(parse_since("2026-10-17", now), Some(1_792_195_200_000));
+        assert_eq!(parse_since("2000-02-29", now), Some(951_782_400_000));
+        assert_eq!(parse_since("yesterday", now), None);
+        assert_eq!(parse_since("2026-13-01", now), None);
+    }
+}
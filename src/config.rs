@@ -1,21 +1,49 @@
 use std::{collections::BTreeMap, fs, path::Path};
 
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 use crate::{
     errors::{AppError, Result},
     fsutil,
     paths::AppPaths,
     provider::ProviderKind,
+    token::TokenSource,
 };
 
-const CURRENT_CONFIG_VERSION: u32 = 1;
+pub const CURRENT_CONFIG_VERSION: u32 = 2;
+
+/// Upgrades a raw config object from version `N` to `N + 1`.
+type Migration = fn(&mut serde_json::Map<String, Value>);
+
+/// Migration chain indexed by source version minus one. Bumping
+/// `CURRENT_CONFIG_VERSION` requires appending the step that reaches it.
+const MIGRATIONS: [Migration; (CURRENT_CONFIG_VERSION - 1) as usize] = [v1_to_v2];
+
+/// Version 2 added `extra_env`, `backup_retention` and provider names that
+/// refer to templates. A release that only knows version 1 would drop them
+/// on its next save, so the bump makes it refuse the file instead. Existing
+/// presets already satisfy the new schema; the step only drops a `null`
+/// `extra_env` left behind by hand edits, which version 2 cannot read.
+fn v1_to_v2(root: &mut serde_json::Map<String, Value>) {
+    let Some(presets) = root.get_mut("presets").and_then(Value::as_object_mut) else {
+        return;
+    };
+    for preset in presets.values_mut().filter_map(Value::as_object_mut) {
+        if preset.get("extra_env").is_some_and(Value::is_null) {
+            preset.remove("extra_env");
+        }
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SwitcherConfig {
     pub version: u32,
     pub active_preset: Option<String>,
     pub presets: BTreeMap<String, Preset>,
+    /// How many settings backups to keep; `0` keeps all of them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup_retention: Option<usize>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,6 +58,8 @@ pub struct Preset {
     pub timeouts: Option<TimeoutConfig>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub flags: Option<FlagConfig>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra_env: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -65,6 +95,7 @@ impl Default for SwitcherConfig {
             version: CURRENT_CONFIG_VERSION,
             active_preset: None,
             presets: BTreeMap::new(),
+            backup_retention: None,
         }
     }
 }
@@ -77,11 +108,53 @@ pub fn load(paths: &AppPaths) -> Result<SwitcherConfig> {
     load_existing_config(&paths.config_path)
 }
 
+/// Reads the config without writing anything back, returning it (migrated in
+/// memory) together with the version stored on disk. `None` when no config exists.
+pub fn inspect(paths: &AppPaths) -> Result<Option<(SwitcherConfig, u32)>> {
+    if !paths.config_path.exists() {
+        return Ok(None);
+    }
+    read_existing_config(&paths.config_path).map(Some)
+}
+
 pub fn save(paths: &AppPaths, config: &SwitcherConfig) -> Result<()> {
     fsutil::write_json_atomic(&paths.config_path, config)
 }
 
+/// Whether `key` is a portable environment variable name (`[A-Za-z_][A-Za-z0-9_]*`),
+/// which is what makes it safe to print unquoted in shell lines.
+pub fn is_env_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl SwitcherConfig {
+    /// An `InvalidEnvKey` error for each preset with a key that is not a plain
+    /// variable name, e.g. from a hand-edited `config.json`.
+    pub fn invalid_env_keys(&self) -> Vec<AppError> {
+        self.presets
+            .iter()
+            .filter_map(|(name, preset)| preset.validate_env_keys(name).err())
+            .collect()
+    }
+}
+
 impl Preset {
+    /// Rejects `extra_env` keys that are not plain variable names.
+    pub fn validate_env_keys(&self, preset_name: &str) -> Result<()> {
+        match self.extra_env.keys().find(|key| !is_env_key(key)) {
+            Some(key) => Err(AppError::InvalidEnvKey {
+                preset: preset_name.to_owned(),
+                key: key.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks everything a preset needs before its env is written anywhere.
     pub fn validate_ready(&self, preset_name: &str) -> Result<()> {
         if self.base_url.trim().is_empty() {
             return Err(AppError::PresetIncomplete {
@@ -89,7 +162,7 @@ impl Preset {
                 field: "base_url",
             });
         }
-        if self.auth_token.trim().is_empty() {
+        if TokenSource::parse(&self.auth_token).target().is_empty() {
             return Err(AppError::PresetIncomplete {
                 preset: preset_name.to_owned(),
                 field: "auth_token",
@@ -113,18 +186,85 @@ impl Preset {
                 field: "models.opus_model",
             });
         }
-        Ok(())
+        self.validate_env_keys(preset_name)
     }
 }
 
+#[cfg(test)]
+impl Preset {
+    /// A complete GLM preset for unit tests; tweak it with struct update syntax.
+    pub fn sample(auth_token: &str) -> Self {
+        Self {
+            provider: ProviderKind::Glm,
+            base_url: "https://open.bigmodel.cn/api/anthropic".to_owned(),
+            auth_token: auth_token.to_owned(),
+            models: ModelConfig {
+                haiku_model: "GLM-4.7".to_owned(),
+                sonnet_model: "GLM-4.7".to_owned(),
+                opus_model: "GLM-4.7".to_owned(),
+            },
+            network: None,
+            timeouts: None,
+            flags: None,
+            extra_env: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct VersionProbe {
+    version: u32,
+}
+
 fn load_existing_config(path: &Path) -> Result<SwitcherConfig> {
+    let (cfg, version) = read_existing_config(path)?;
+    // Only commands that emit a broken preset's env fail; the rest stay usable
+    // so the preset can be edited or removed.
+    for err in cfg.invalid_env_keys() {
+        eprintln!("Warning: {err}");
+    }
+    if version != CURRENT_CONFIG_VERSION {
+        fsutil::backup_if_exists(path)?;
+        fsutil::write_json_atomic(path, &cfg)?;
+    }
+    Ok(cfg)
+}
+
+fn read_existing_config(path: &Path) -> Result<(SwitcherConfig, u32)> {
     let raw = fs::read_to_string(path).map_err(|err| AppError::io(path, err))?;
+    let mut root: Value = serde_json::from_str(&raw).map_err(|err| AppError::json(path, err))?;
+    let VersionProbe { version } =
+        VersionProbe::deserialize(&root).map_err(|err| AppError::json(path, err))?;
+
+    if version > CURRENT_CONFIG_VERSION {
+        return Err(AppError::ConfigVersionTooNew {
+            found: version,
+            supported: CURRENT_CONFIG_VERSION,
+        });
+    }
+    if version < CURRENT_CONFIG_VERSION {
+        apply_migrations(path, &mut root, version, &MIGRATIONS)?;
+    }
     let cfg: SwitcherConfig =
-        serde_json::from_str(&raw).map_err(|err| AppError::json(path, err))?;
-    if cfg.version != CURRENT_CONFIG_VERSION {
-        return Err(AppError::UnsupportedConfigVersion(cfg.version));
+        serde_json::from_value(root).map_err(|err| AppError::json(path, err))?;
+    Ok((cfg, version))
+}
+
+fn apply_migrations(path: &Path, root: &mut Value, from: u32, steps: &[Migration]) -> Result<()> {
+    let root_obj = root
+        .as_object_mut()
+        .ok_or_else(|| AppError::invalid_json_root(path))?;
+    let first_step = from
+        .checked_sub(1)
+        .map(|idx| idx as usize)
+        .filter(|idx| *idx < steps.len())
+        .ok_or(AppError::UnsupportedConfigVersion(from))?;
+
+    for (idx, step) in steps.iter().enumerate().skip(first_step) {
+        step(root_obj);
+        root_obj.insert("version".to_owned(), Value::from(idx as u32 + 2));
     }
-    Ok(cfg)
+    Ok(())
 }
 
 #[cfg(test)]
@@ -138,20 +278,21 @@ mod tests {
         assert!(cfg.active_preset.is_none());
     }
 
+    #[test]
+    fn env_keys_must_be_plain_variable_names() {
+        for key in ["NO_PROXY", "_private", "a1"] {
+            assert!(is_env_key(key), "{key}");
+        }
+        for key in ["", "1ABC", "A-B", "A B", "X;rm -rf ~", "A=B", "$(id)", "ÄB"] {
+            assert!(!is_env_key(key), "{key}");
+        }
+    }
+
     #[test]
     fn validate_ready_rejects_missing_required_fields() {
         let preset = Preset {
-            provider: ProviderKind::Glm,
             base_url: String::new(),
-            auth_token: "token".to_owned(),
-            models: ModelConfig {
-                haiku_model: "GLM-4.7".to_owned(),
-                sonnet_model: "GLM-4.7".to_owned(),
-                opus_model: "GLM-4.7".to_owned(),
-            },
-            network: None,
-            timeouts: None,
-            flags: None,
+            ..Preset::sample("token")
         };
 
         let err = preset.validate_ready("glm").expect_err("expected error");
@@ -163,4 +304,60 @@ mod tests {
             } if preset == "glm"
         ));
     }
+
+    #[test]
+    fn v1_config_round_trips_through_migration_unchanged() {
+        let tmp = tempfile::TempDir::new().expect("tempdir");
+        let path = tmp.path().join("config.json");
+        let fixture = include_str!("../tests/fixtures/config/v1.json");
+        fs::write(&path, fixture).expect("write fixture");
+
+        let cfg = load_existing_config(&path).expect("load v1");
+        assert_eq!(cfg.version, CURRENT_CONFIG_VERSION);
+        let mut expected: Value = serde_json::from_str(fixture).expect("fixture json");
+        expected["version"] = Value::from(CURRENT_CONFIG_VERSION);
+        let rewritten: Value =
+            serde_json::from_str(&fs::read_to_string(&path).expect("read")).expect("json");
+        assert_eq!(rewritten, expected);
+        assert_eq!(serde_json::to_value(&cfg).expect("serialize"), expected);
+
+        let mut hand_edited = serde_json::json!({
+            "version": 1,
+            "presets": { "p": { "extra_env": null } },
+        });
+        apply_migrations(&path, &mut hand_edited, 1, &MIGRATIONS).expect("migrate");
+        assert_eq!(
+            hand_edited,
+            serde_json::json!({ "version": 2, "presets": { "p": {} } })
+        );
+    }
+
+    #[test]
+    fn apply_migrations_runs_each_step_from_source_version() {
+        fn add_marker(root: &mut serde_json::Map<String, Value>) {
+            root.insert("step_one".to_owned(), Value::Bool(true));
+        }
+        fn rename_marker(root: &mut serde_json::Map<String, Value>) {
+            if let Some(value) = root.remove("step_one") {
+                root.insert("step_two".to_owned(), value);
+            }
+        }
+        let steps: [Migration; 2] = [add_marker, rename_marker];
+        let path = Path::new("config.json");
+
+        let mut from_v1 = serde_json::json!({ "version": 1 });
+        apply_migrations(path, &mut from_v1, 1, &steps).expect("migrate v1");
+        assert_eq!(
+            from_v1,
+            serde_json::json!({ "version": 3, "step_two": true })
+        );
+
+        let mut from_v2 = serde_json::json!({ "version": 2 });
+        apply_migrations(path, &mut from_v2, 2, &steps).expect("migrate v2");
+        assert_eq!(from_v2, serde_json::json!({ "version": 3 }));
+
+        let mut unknown = serde_json::json!({ "version": 0 });
+        let err = apply_migrations(path, &mut unknown, 0, &steps).expect_err("expected error");
+        assert!(matches!(err, AppError::UnsupportedConfigVersion(0)));
+    }
 }
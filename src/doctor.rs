@@ -0,0 +1,300 @@
+use std::{env, fs, path::Path};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    BASH_PERMISSION_RULE, SWITCHMODEL_TEMPLATE,
+    config::{self, CURRENT_CONFIG_VERSION, SwitcherConfig},
+    fsutil, marker,
+    paths::AppPaths,
+    provider::{Catalog, ProviderKind},
+    settings::{self, CONFLICTING_API_KEY, EnvChange},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl Status {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Pass => "pass",
+            Self::Warn => "warn",
+            Self::Fail => "fail",
+        }
+    }
+}
+
+/// Outcome of a single diagnostic.
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub status: Status,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, status: Status, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+pub fn run(paths: &AppPaths) -> Vec<Check> {
+    let mut checks = vec![check_paths(paths)];
+
+    let cfg = check_config(paths, &mut checks);
+    if let Some(cfg) = &cfg {
+        check_active_preset(paths, cfg, &mut checks);
+    }
+    checks.push(check_api_key(paths));
+    checks.push(check_provider_templates(paths));
+    checks.push(check_slash_command(paths));
+    checks.push(check_permission_rule(paths));
+
+    if let Some(home) = paths.config_path.parent() {
+        checks.push(check_permissions(
+            "ccswitcher home permissions",
+            home,
+            0o700,
+        ));
+    }
+    checks.push(check_permissions(
+        "config.json permissions",
+        &paths.config_path,
+        0o600,
+    ));
+    checks.push(check_permissions(
+        "settings.json permissions",
+        &paths.settings_path,
+        0o600,
+    ));
+    checks
+}
+
+fn check_paths(paths: &AppPaths) -> Check {
+    let source = |var: &str| {
+        if env::var_os(var).is_some() {
+            format!("from {var}")
+        } else {
+            "default".to_owned()
+        }
+    };
+    let home = paths.config_path.parent().unwrap_or(Path::new("."));
+    Check::new(
+        "paths",
+        Status::Pass,
+        format!(
+            "ccswitcher home {} ({}), Claude home {} ({})",
+            home.display(),
+            source("CCSWITCHER_HOME"),
+            paths.claude_home.display(),
+            source("CLAUDE_HOME")
+        ),
+    )
+}
+
+fn check_provider_templates(paths: &AppPaths) -> Check {
+    const NAME: &str = "provider templates";
+    let catalog = match Catalog::load(paths) {
+        Ok(catalog) => catalog,
+        Err(err) => return Check::new(NAME, Status::Fail, err.to_string()),
+    };
+    if catalog.broken().is_empty() {
+        let user = catalog
+            .entries()
+            .iter()
+            .filter(|(kind, _)| matches!(kind, ProviderKind::User(_)))
+            .count();
+        return Check::new(NAME, Status::Pass, format!("{user} user template(s)"));
+    }
+    let problems: Vec<String> = catalog.broken().iter().map(|err| err.to_string()).collect();
+    Check::new(NAME, Status::Warn, problems.join(" "))
+}
+
+fn check_config(paths: &AppPaths, checks: &mut Vec<Check>) -> Option<SwitcherConfig> {
+    let (check, cfg) = match config::inspect(paths) {
+        Ok(None) => (
+            Check::new("config", Status::Pass, "no config.json yet"),
+            None,
+        ),
+        Ok(Some((cfg, version))) if version < CURRENT_CONFIG_VERSION => (
+            Check::new(
+                "config",
+                Status::Warn,
+                format!(
+                    "version {version} will be migrated to {CURRENT_CONFIG_VERSION} on the next command"
+                ),
+            ),
+            Some(cfg),
+        ),
+        Ok(Some((cfg, version))) => {
+            let invalid: Vec<String> = cfg
+                .invalid_env_keys()
+                .iter()
+                .map(|err| err.to_string())
+                .collect();
+            let check = if invalid.is_empty() {
+                Check::new(
+                    "config",
+                    Status::Pass,
+                    format!("version {version}, {} preset(s)", cfg.presets.len()),
+                )
+            } else {
+                Check::new("config", Status::Warn, invalid.join(" "))
+            };
+            (check, Some(cfg))
+        }
+        Err(err) => (Check::new("config", Status::Fail, err.to_string()), None),
+    };
+    checks.push(check);
+    cfg
+}
+
+fn check_active_preset(paths: &AppPaths, cfg: &SwitcherConfig, checks: &mut Vec<Check>) {
+    let Some(name) = cfg.active_preset.as_deref() else {
+        checks.push(Check::new(
+            "active preset",
+            Status::Pass,
+            "none (official defaults)",
+        ));
+        return;
+    };
+    let Some(preset) = cfg.presets.get(name) else {
+        checks.push(Check::new(
+            "active preset",
+            Status::Fail,
+            format!("'{name}' is not defined in config.json; run `ccswitcher use` or `reset`"),
+        ));
+        return;
+    };
+    checks.push(Check::new("active preset", Status::Pass, name));
+
+    let drift = match settings::drift(paths, &marker::as_applied(paths, name, preset)) {
+        Ok(changes) if changes.is_empty() => Check::new(
+            "settings drift",
+            Status::Pass,
+            format!("settings.json env matches '{name}'"),
+        ),
+        Ok(changes) => Check::new(
+            "settings drift",
+            Status::Warn,
+            format!(
+                "settings.json env differs from '{name}' for {}; run `ccswitcher sync --reapply` or `sync --capture`",
+                changes
+                    .iter()
+                    .map(EnvChange::key)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        ),
+        Err(err) => Check::new("settings drift", Status::Fail, err.to_string()),
+    };
+    checks.push(drift);
+}
+
+fn check_api_key(paths: &AppPaths) -> Check {
+    let mut found = Vec::new();
+    if settings::live_env(paths).is_ok_and(|env| env.contains_key(CONFLICTING_API_KEY)) {
+        found.push("settings.json env");
+    }
+    if env::var_os(CONFLICTING_API_KEY).is_some() {
+        found.push("the process environment");
+    }
+    if found.is_empty() {
+        return Check::new(CONFLICTING_API_KEY, Status::Pass, "not set");
+    }
+    Check::new(
+        CONFLICTING_API_KEY,
+        Status::Warn,
+        format!(
+            "set in {}; it can override the preset's ANTHROPIC_AUTH_TOKEN",
+            found.join(" and ")
+        ),
+    )
+}
+
+fn check_slash_command(paths: &AppPaths) -> Check {
+    let path = paths.switchmodel_command_path();
+    match fs::read_to_string(&path) {
+        Ok(installed) if installed.trim_end() == SWITCHMODEL_TEMPLATE.trim_end() => Check::new(
+            "slash command",
+            Status::Pass,
+            format!("{} is up to date", path.display()),
+        ),
+        Ok(_) => Check::new(
+            "slash command",
+            Status::Warn,
+            format!(
+                "{} differs from this version; run `ccswitcher install`",
+                path.display()
+            ),
+        ),
+        Err(_) => Check::new(
+            "slash command",
+            Status::Warn,
+            "/switchmodel is not installed; run `ccswitcher install`",
+        ),
+    }
+}
+
+fn check_permission_rule(paths: &AppPaths) -> Check {
+    let path = &paths.settings_local_path;
+    let allowed = fs::read_to_string(path)
+        .ok()
+        .and_then(|raw| serde_json::from_str::<Value>(&raw).ok())
+        .and_then(|root| root.pointer("/permissions/allow").cloned())
+        .and_then(|allow| allow.as_array().cloned())
+        .is_some_and(|allow| {
+            allow
+                .iter()
+                .any(|rule| rule.as_str() == Some(BASH_PERMISSION_RULE))
+        });
+    if allowed {
+        Check::new(
+            "permission rule",
+            Status::Pass,
+            format!("{BASH_PERMISSION_RULE} is allowed"),
+        )
+    } else {
+        Check::new(
+            "permission rule",
+            Status::Warn,
+            format!(
+                "{BASH_PERMISSION_RULE} is missing from {}; run `ccswitcher install`",
+                path.display()
+            ),
+        )
+    }
+}
+
+/// Warns when `path` grants any access to group or others.
+fn check_permissions(name: &str, path: &Path, recommended: u32) -> Check {
+    if !path.exists() {
+        return Check::new(
+            name,
+            Status::Pass,
+            format!("{} does not exist", path.display()),
+        );
+    }
+    match fsutil::file_mode(path) {
+        None => Check::new(name, Status::Pass, "not checked on this platform"),
+        Some(mode) if mode & 0o077 == 0 => Check::new(name, Status::Pass, format!("{mode:04o}")),
+        Some(mode) => Check::new(
+            name,
+            Status::Warn,
+            format!(
+                "mode {mode:04o} is accessible by group/others; run `chmod {recommended:o} {}`",
+                path.display()
+            ),
+        ),
+    }
+}
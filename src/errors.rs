@@ -1,5 +1,6 @@
 use std::path::{Path, PathBuf};
 
+use serde_json::Value;
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, AppError>;
@@ -14,10 +15,78 @@ pub enum AppError {
     PresetNotFound(String),
     #[error("Cannot remove active preset '{0}'. Switch presets first.")]
     CannotRemoveActivePreset(String),
+    #[error("Backup '{0}' was not found.")]
+    BackupNotFound(String),
+    #[error("Nothing to undo.")]
+    NothingToUndo,
+    #[error("Nothing to redo.")]
+    NothingToRedo,
     #[error("Unsupported config version {0}.")]
     UnsupportedConfigVersion(u32),
+    #[error(
+        "Config version {found} is newer than this ccswitcher supports (version {supported}). Upgrade ccswitcher."
+    )]
+    ConfigVersionTooNew { found: u32, supported: u32 },
+    #[error("Unsupported preset bundle version {0}. Upgrade ccswitcher to import it.")]
+    UnsupportedBundleVersion(u32),
     #[error("Preset '{preset}' is missing required field '{field}'.")]
     PresetIncomplete { preset: String, field: &'static str },
+    #[error("Could not resolve auth token from {origin}: {reason}.")]
+    TokenUnavailable { origin: String, reason: String },
+    #[error(
+        "No project found from '{start}': expected a .claude directory or .git in it or a parent directory."
+    )]
+    ProjectNotFound { start: PathBuf },
+    #[error(
+        "Refusing to write the auth token into '{path}', which is usually committed. Use `--scope local`, or pass --force."
+    )]
+    ProjectScopeNeedsForce { path: PathBuf },
+    #[error("Unknown provider '{0}'. Run `ccswitcher providers` to list the available ones.")]
+    UnknownProvider(String),
+    #[error("Provider '{provider}' requires '{field}' to be given explicitly.")]
+    ProviderFieldRequired { provider: String, field: String },
+    #[error("Preset '{preset}' failed the connectivity check: {failures}.")]
+    /// `report` holds the per-model results so `--json` output keeps them.
+    ConnectivityCheckFailed {
+        preset: String,
+        failures: String,
+        report: Value,
+    },
+    #[error(
+        "Imported preset '{preset}' reads its auth token through '{kind}:'. Re-run with --allow-token-commands if you trust the bundle."
+    )]
+    UntrustedTokenSource { preset: String, kind: &'static str },
+    /// `report` is the command's JSON output, kept so `--json` still shows every check.
+    #[error("{failures} doctor check(s) failed.")]
+    DoctorChecksFailed { failures: usize, report: Value },
+    #[error("Invalid provider template '{path}': {reason}.")]
+    InvalidProviderTemplate { path: PathBuf, reason: String },
+    #[error(
+        "Preset '{preset}' sets invalid env key '{key}'; keys must match [A-Za-z_][A-Za-z0-9_]*."
+    )]
+    InvalidEnvKey { preset: String, key: String },
+    #[error("Invalid marker file '{path}': {reason}.")]
+    InvalidMarker { path: PathBuf, reason: String },
+    #[error(
+        "Marker file '{path}' is new or has changed since it was approved. Review it, then run `ccswitcher allow` to let `auto` use it."
+    )]
+    MarkerNotAllowed { path: PathBuf },
+    #[error(
+        "Preset '{preset}' is named by the approved marker file '{path}'. Point the marker at the new name and approve it again, or withdraw it with `ccswitcher deny`, before renaming."
+    )]
+    PresetNamedByMarker { preset: String, path: PathBuf },
+    #[error("Token vault is not initialized. Run `ccswitcher vault init` first.")]
+    VaultNotInitialized,
+    #[error("Token vault already exists.")]
+    VaultAlreadyInitialized,
+    #[error(
+        "Token vault is locked. Set CCSWITCHER_PASSPHRASE or run from an interactive terminal."
+    )]
+    VaultLocked,
+    #[error("Vault passphrase is incorrect.")]
+    VaultPassphraseIncorrect,
+    #[error("Token vault at '{path}' is corrupt or uses an unsupported format.")]
+    VaultCorrupt { path: PathBuf },
     #[error("JSON root in '{path}' must be an object.")]
     InvalidJsonRoot { path: PathBuf },
     #[error("Failed to write command output: {source}")]
@@ -53,6 +122,130 @@ impl AppError {
         Self::Output { source }
     }
 
+    /// Stable machine-readable identifier for the error kind.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::MissingHomeDirectory => "missing_home_directory",
+            Self::PresetAlreadyExists(_) => "preset_already_exists",
+            Self::PresetNotFound(_) => "preset_not_found",
+            Self::CannotRemoveActivePreset(_) => "cannot_remove_active_preset",
+            Self::BackupNotFound(_) => "backup_not_found",
+            Self::NothingToUndo => "nothing_to_undo",
+            Self::NothingToRedo => "nothing_to_redo",
+            Self::UnsupportedConfigVersion(_) => "unsupported_config_version",
+            Self::ConfigVersionTooNew { .. } => "config_version_too_new",
+            Self::UnsupportedBundleVersion(_) => "unsupported_bundle_version",
+            Self::UnknownProvider(_) => "unknown_provider",
+            Self::ProviderFieldRequired { .. } => "provider_field_required",
+            Self::InvalidProviderTemplate { .. } => "invalid_provider_template",
+            Self::InvalidEnvKey { .. } => "invalid_env_key",
+            Self::PresetNamedByMarker { .. } => "preset_named_by_marker",
+            Self::ConnectivityCheckFailed { .. } => "connectivity_check_failed",
+            Self::UntrustedTokenSource { .. } => "untrusted_token_source",
+            Self::DoctorChecksFailed { .. } => "doctor_checks_failed",
+            Self::ProjectScopeNeedsForce { .. } => "project_scope_needs_force",
+            Self::PresetIncomplete { .. } => "preset_incomplete",
+            Self::TokenUnavailable { .. } => "token_unavailable",
+            Self::ProjectNotFound { .. } => "project_not_found",
+            Self::InvalidMarker { .. } => "invalid_marker",
+            Self::MarkerNotAllowed { .. } => "marker_not_allowed",
+            Self::VaultNotInitialized => "vault_not_initialized",
+            Self::VaultAlreadyInitialized => "vault_already_initialized",
+            Self::VaultLocked => "vault_locked",
+            Self::VaultPassphraseIncorrect => "vault_passphrase_incorrect",
+            Self::VaultCorrupt { .. } => "vault_corrupt",
+            Self::InvalidJsonRoot { .. } => "invalid_json_root",
+            Self::Output { .. } => "output",
+            Self::Io { .. } => "io",
+            Self::Json { .. } => "json",
+        }
+    }
+
+    /// Process exit code for the error kind. Codes are stable across releases:
+    ///
+    /// | Code | Error |
+    /// |------|-------|
+    /// | 2    | invalid command-line usage (reported by clap) |
+    /// | 3    | `MissingHomeDirectory` |
+    /// | 10   | `PresetNotFound` |
+    /// | 11   | `PresetAlreadyExists` |
+    /// | 12   | `CannotRemoveActivePreset` |
+    /// | 13   | `PresetIncomplete` |
+    /// | 14   | `BackupNotFound` |
+    /// | 15   | `NothingToUndo` |
+    /// | 16   | `NothingToRedo` |
+    /// | 17   | `TokenUnavailable` |
+    /// | 18   | `ProjectNotFound` |
+    /// | 19   | `InvalidMarker` |
+    /// | 20   | `UnsupportedConfigVersion` |
+    /// | 21   | `ConfigVersionTooNew` |
+    /// | 22   | `UnsupportedBundleVersion` |
+    /// | 23   | `UnknownProvider` |
+    /// | 24   | `ProviderFieldRequired` |
+    /// | 25   | `ConnectivityCheckFailed` |
+    /// | 26   | `UntrustedTokenSource` |
+    /// | 27   | `DoctorChecksFailed` |
+    /// | 28   | `ProjectScopeNeedsForce` |
+    /// | 29   | `MarkerNotAllowed` |
+    /// | 30   | `InvalidJsonRoot` |
+    /// | 31   | `Json` |
+    /// | 32   | `InvalidProviderTemplate` |
+    /// | 33   | `InvalidEnvKey` |
+    /// | 34   | `PresetNamedByMarker` |
+    /// | 40   | `Io` |
+    /// | 41   | `Output` |
+    /// | 50   | `VaultNotInitialized` |
+    /// | 51   | `VaultAlreadyInitialized` |
+    /// | 52   | `VaultLocked` |
+    /// | 53   | `VaultPassphraseIncorrect` |
+    /// | 54   | `VaultCorrupt` |
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::MissingHomeDirectory => 3,
+            Self::PresetNotFound(_) => 10,
+            Self::PresetAlreadyExists(_) => 11,
+            Self::CannotRemoveActivePreset(_) => 12,
+            Self::PresetIncomplete { .. } => 13,
+            Self::BackupNotFound(_) => 14,
+            Self::NothingToUndo => 15,
+            Self::NothingToRedo => 16,
+            Self::TokenUnavailable { .. } => 17,
+            Self::ProjectNotFound { .. } => 18,
+            Self::InvalidMarker { .. } => 19,
+            Self::UnsupportedConfigVersion(_) => 20,
+            Self::ConfigVersionTooNew { .. } => 21,
+            Self::UnsupportedBundleVersion(_) => 22,
+            Self::UnknownProvider(_) => 23,
+            Self::ProviderFieldRequired { .. } => 24,
+            Self::ConnectivityCheckFailed { .. } => 25,
+            Self::UntrustedTokenSource { .. } => 26,
+            Self::DoctorChecksFailed { .. } => 27,
+            Self::ProjectScopeNeedsForce { .. } => 28,
+            Self::MarkerNotAllowed { .. } => 29,
+            Self::InvalidJsonRoot { .. } => 30,
+            Self::Json { .. } => 31,
+            Self::InvalidProviderTemplate { .. } => 32,
+            Self::InvalidEnvKey { .. } => 33,
+            Self::PresetNamedByMarker { .. } => 34,
+            Self::Io { .. } => 40,
+            Self::Output { .. } => 41,
+            Self::VaultNotInitialized => 50,
+            Self::VaultAlreadyInitialized => 51,
+            Self::VaultLocked => 52,
+            Self::VaultPassphraseIncorrect => 53,
+            Self::VaultCorrupt { .. } => 54,
+        }
+    }
+
+    /// Fields a failed command still reports next to `error` in JSON mode.
+    pub fn report(&self) -> Option<&Value> {
+        match self {
+            Self::ConnectivityCheckFailed { report, .. }
+            | Self::DoctorChecksFailed { report, .. } => Some(report),
+            _ => None,
+        }
+    }
+
     pub fn invalid_json_root(path: impl AsRef<Path>) -> Self {
         Self::InvalidJsonRoot {
             path: path.as_ref().to_path_buf(),
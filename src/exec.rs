@@ -0,0 +1,38 @@
+use std::{collections::BTreeMap, process::Command};
+
+use crate::{
+    errors::{AppError, Result},
+    settings::{CONFLICTING_API_KEY, OVERRIDE_ENV_KEYS},
+};
+
+/// Runs `command` with `env` added to the inherited environment. Managed keys
+/// the preset leaves unset, and `ANTHROPIC_API_KEY`, are removed first so the
+/// caller's shell cannot mix another provider into the preset.
+///
+/// On Unix the current process is replaced, so the child's exit status and
+/// signals reach the caller unchanged; elsewhere ccswitcher waits and exits
+/// with the child's code. Only returns when the command cannot be started.
+pub fn replace_process(command: &[String], env: &BTreeMap<String, String>) -> Result<()> {
+    let (program, args) = command
+        .split_first()
+        .expect("clap requires at least one command word");
+    let mut child = Command::new(program);
+    child.args(args);
+    for key in OVERRIDE_ENV_KEYS.into_iter().chain([CONFLICTING_API_KEY]) {
+        child.env_remove(key);
+    }
+    child.envs(env);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        let err = child.exec();
+        Err(AppError::io(program, err))
+    }
+
+    #[cfg(not(unix))]
+    {
+        let status = child.status().map_err(|err| AppError::io(program, err))?;
+        std::process::exit(status.code().unwrap_or(1));
+    }
+}
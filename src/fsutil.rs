@@ -9,10 +9,44 @@ use serde::Serialize;
 
 use crate::errors::{AppError, Result};
 
+/// Mode for files ccswitcher creates; they may contain auth tokens.
+#[cfg(unix)]
+const PRIVATE_FILE_MODE: u32 = 0o600;
+#[cfg(unix)]
+const PRIVATE_DIR_MODE: u32 = 0o700;
+
 pub fn ensure_directory(path: &Path) -> Result<()> {
     fs::create_dir_all(path).map_err(|err| AppError::io(path, err))
 }
 
+/// Like [`ensure_directory`], but a newly created leaf directory is only accessible by the owner.
+pub fn ensure_private_directory(path: &Path) -> Result<()> {
+    if path.is_dir() {
+        return Ok(());
+    }
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, PRIVATE_DIR_MODE);
+    builder.create(path).map_err(|err| AppError::io(path, err))
+}
+
+/// Unix permission bits of `path`, or `None` when it is missing or the platform has none.
+pub fn file_mode(path: &Path) -> Option<u32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::metadata(path)
+            .ok()
+            .map(|meta| meta.permissions().mode() & 0o777)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        None
+    }
+}
+
 pub fn backup_if_exists(path: &Path) -> Result<Option<PathBuf>> {
     if !path.exists() {
         return Ok(None);
@@ -31,16 +65,48 @@ pub fn backup_if_exists(path: &Path) -> Result<Option<PathBuf>> {
     Ok(Some(backup_path))
 }
 
+/// Writes `path` atomically as an owner-only (0600) file.
 pub fn write_json_atomic<T: Serialize>(path: &Path, payload: &T) -> Result<()> {
     let bytes = serde_json::to_vec_pretty(payload).map_err(|err| AppError::json(path, err))?;
-    write_bytes_atomic(path, &bytes)
+    write_bytes_atomic(path, &bytes, false)
 }
 
 pub fn write_text_atomic(path: &Path, content: &str) -> Result<()> {
-    write_bytes_atomic(path, content.as_bytes())
+    write_bytes_atomic(path, content.as_bytes(), false)
+}
+
+/// Writes a file owned by Claude Code, keeping its current mode; new files are 0600.
+pub fn write_json_atomic_keeping_mode<T: Serialize>(path: &Path, payload: &T) -> Result<()> {
+    let bytes = serde_json::to_vec_pretty(payload).map_err(|err| AppError::json(path, err))?;
+    write_bytes_atomic(path, &bytes, true)
 }
 
-fn write_bytes_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
+pub fn write_text_atomic_keeping_mode(path: &Path, content: &str) -> Result<()> {
+    write_bytes_atomic(path, content.as_bytes(), true)
+}
+
+pub fn append_line(path: &Path, line: &str) -> Result<()> {
+    let parent = path.parent().unwrap_or_else(|| Path::new("."));
+    ensure_directory(parent)?;
+
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, PRIVATE_FILE_MODE);
+    let mut file = options.open(path).map_err(|err| AppError::io(path, err))?;
+    writeln!(file, "{line}").map_err(|err| AppError::io(path, err))
+}
+
+/// Stable FNV-1a hash of a path, for naming per-path files.
+pub fn path_hash(path: &Path) -> u64 {
+    path.to_string_lossy()
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+fn write_bytes_atomic(path: &Path, bytes: &[u8], keep_mode: bool) -> Result<()> {
     let parent = path.parent().unwrap_or_else(|| Path::new("."));
     ensure_directory(parent)?;
 
@@ -53,11 +119,18 @@ fn write_bytes_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
             .map_or(0, |d| d.as_nanos())
     ));
 
-    let mut file = OpenOptions::new()
-        .create_new(true)
-        .write(true)
+    let mut options = OpenOptions::new();
+    options.create_new(true).write(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, PRIVATE_FILE_MODE);
+    let mut file = options
         .open(&tmp_path)
         .map_err(|err| AppError::io(&tmp_path, err))?;
+    if keep_mode && let Ok(existing) = fs::metadata(path) {
+        // The open mode is filtered by the umask, so apply the old mode explicitly.
+        file.set_permissions(existing.permissions())
+            .map_err(|err| AppError::io(&tmp_path, err))?;
+    }
     file.write_all(bytes)
         .and_then(|_| file.write_all(b"\n"))
         .and_then(|_| file.sync_all())
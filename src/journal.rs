@@ -0,0 +1,202 @@
+use std::{collections::BTreeMap, fs};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    config::SwitcherConfig,
+    errors::{AppError, Result},
+    fsutil,
+    paths::AppPaths,
+    settings, state, token,
+};
+
+/// Oldest entries are dropped beyond this many recorded switches.
+const MAX_ENTRIES: usize = 50;
+
+/// Journals without a version stored the whole `env` block, resolved auth
+/// token included; they are discarded on load.
+const JOURNAL_VERSION: u32 = 2;
+
+const AUTH_TOKEN_KEY: &str = "ANTHROPIC_AUTH_TOKEN";
+
+/// What a switch operation touched: the active preset, the values of the env
+/// keys ccswitcher owned at that point, and what those keys held before
+/// ccswitcher took them over. The auth token is kept as the active preset's
+/// token reference and resolved again on restore, so no secret resolved from
+/// `env:`, `file:`, `cmd:` or `vault:` ends up in the journal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub active_preset: Option<String>,
+    /// Owned keys mapped to their value, `None` when absent.
+    pub env: BTreeMap<String, Option<Value>>,
+    #[serde(default)]
+    pub owned_env: BTreeMap<String, Option<Value>>,
+}
+
+impl Snapshot {
+    /// The recorded values with the auth token reference resolved.
+    pub fn resolved_env(&self, paths: &AppPaths) -> Result<BTreeMap<String, Option<Value>>> {
+        let mut env = self.env.clone();
+        if let Some(Some(Value::String(reference))) = env.get_mut(AUTH_TOKEN_KEY) {
+            *reference = token::resolve(paths, reference)?;
+        }
+        Ok(env)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub operation: String,
+    pub before: Snapshot,
+    pub after: Snapshot,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Journal {
+    version: u32,
+    entries: Vec<JournalEntry>,
+    /// Number of entries currently applied; entries past it can be redone.
+    cursor: usize,
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Self {
+            version: JOURNAL_VERSION,
+            entries: Vec::new(),
+            cursor: 0,
+        }
+    }
+}
+
+pub fn capture(paths: &AppPaths, cfg: &SwitcherConfig) -> Result<Snapshot> {
+    let live = settings::live_env(paths)?;
+    let owned_env = state::load(paths)?.owned_env;
+    let token_reference = cfg
+        .active_preset
+        .as_ref()
+        .and_then(|name| cfg.presets.get(name))
+        .map(|preset| preset.auth_token.clone());
+
+    let mut env = BTreeMap::new();
+    for key in owned_env.keys() {
+        let value = match (key.as_str(), &token_reference) {
+            (AUTH_TOKEN_KEY, Some(reference)) if live.contains_key(key) => {
+                Some(Value::String(reference.clone()))
+            }
+            // A token no preset explains is not journaled; restoring leaves it as it is.
+            (AUTH_TOKEN_KEY, _) if live.contains_key(key) => continue,
+            _ => live.get(key).cloned(),
+        };
+        env.insert(key.clone(), value);
+    }
+    Ok(Snapshot {
+        active_preset: cfg.active_preset.clone(),
+        env,
+        owned_env,
+    })
+}
+
+/// Appends a switch, discarding anything that was undone and not redone.
+pub fn record(
+    paths: &AppPaths,
+    operation: String,
+    before: Snapshot,
+    after: Snapshot,
+) -> Result<()> {
+    let mut journal = load(paths)?;
+    journal.entries.truncate(journal.cursor);
+    journal.entries.push(JournalEntry {
+        operation,
+        before,
+        after,
+    });
+    if journal.entries.len() > MAX_ENTRIES {
+        let excess = journal.entries.len() - MAX_ENTRIES;
+        journal.entries.drain(..excess);
+    }
+    journal.cursor = journal.entries.len();
+    save(paths, &journal)
+}
+
+/// Restores the entry before the cursor with `restore(entry.before)` and moves
+/// the cursor back. The cursor stays put when restoring fails.
+pub fn step_back(
+    paths: &AppPaths,
+    restore: impl FnOnce(&Snapshot) -> Result<()>,
+) -> Result<JournalEntry> {
+    let mut journal = load(paths)?;
+    if journal.cursor == 0 {
+        return Err(AppError::NothingToUndo);
+    }
+    let entry = journal.entries[journal.cursor - 1].clone();
+    restore(&entry.before)?;
+    journal.cursor -= 1;
+    save(paths, &journal)?;
+    Ok(entry)
+}
+
+/// Re-applies the entry at the cursor with `restore(entry.after)` and moves
+/// the cursor forward. The cursor stays put when restoring fails.
+pub fn step_forward(
+    paths: &AppPaths,
+    restore: impl FnOnce(&Snapshot) -> Result<()>,
+) -> Result<JournalEntry> {
+    let mut journal = load(paths)?;
+    let Some(entry) = journal.entries.get(journal.cursor).cloned() else {
+        return Err(AppError::NothingToRedo);
+    };
+    restore(&entry.after)?;
+    journal.cursor += 1;
+    save(paths, &journal)?;
+    Ok(entry)
+}
+
+/// Rewrites snapshots naming `old` as their active preset to name `new`, so
+/// undo and redo keep working after a rename.
+pub fn rename_preset(paths: &AppPaths, old: &str, new: &str) -> Result<()> {
+    if !paths.journal_path.exists() {
+        return Ok(());
+    }
+    let mut journal = load(paths)?;
+    let mut changed = false;
+    for snapshot in journal
+        .entries
+        .iter_mut()
+        .flat_map(|entry| [&mut entry.before, &mut entry.after])
+    {
+        if snapshot.active_preset.as_deref() == Some(old) {
+            snapshot.active_preset = Some(new.to_owned());
+            changed = true;
+        }
+    }
+    if !changed {
+        return Ok(());
+    }
+    save(paths, &journal)
+}
+
+fn load(paths: &AppPaths) -> Result<Journal> {
+    if !paths.journal_path.exists() {
+        return Ok(Journal::default());
+    }
+
+    let raw = fs::read_to_string(&paths.journal_path)
+        .map_err(|err| AppError::io(&paths.journal_path, err))?;
+    let root: Value =
+        serde_json::from_str(&raw).map_err(|err| AppError::json(&paths.journal_path, err))?;
+    if root.get("version").and_then(Value::as_u64) != Some(u64::from(JOURNAL_VERSION)) {
+        let journal = Journal::default();
+        save(paths, &journal)?;
+        return Ok(journal);
+    }
+    let mut journal: Journal =
+        serde_json::from_value(root).map_err(|err| AppError::json(&paths.journal_path, err))?;
+    journal.cursor = journal.cursor.min(journal.entries.len());
+    Ok(journal)
+}
+
+fn save(paths: &AppPaths, journal: &Journal) -> Result<()> {
+    fsutil::write_json_atomic(&paths.journal_path, journal)
+}
@@ -1,50 +1,171 @@
+mod backups;
+mod bundle;
 mod cli;
+mod clock;
 mod config;
+mod doctor;
 mod errors;
+mod exec;
 mod fsutil;
+mod history;
+mod journal;
+mod marker;
 mod paths;
+mod probe;
 mod provider;
+mod report;
 mod settings;
+mod shell;
+mod state;
+mod token;
+mod vault;
 
-use std::io::Write;
+use std::{io::Write, path::PathBuf};
 
 use clap::Parser;
-use cli::{Cli, Commands};
+use cli::{Cli, Commands, OutputFormat};
 use config::{Preset, SwitcherConfig};
 pub use errors::{AppError, Result};
+use paths::Scope;
 use provider::ProviderKind;
 use serde_json::{Value, json};
+use settings::OVERRIDE_ENV_KEYS;
+use token::TokenSource;
 
-pub fn run() -> Result<()> {
+/// `/switchmodel` slash command written by `ccswitcher install`.
+const SWITCHMODEL_TEMPLATE: &str = include_str!("../templates/switchmodel.md");
+/// Permission rule that lets the slash command run ccswitcher without prompting.
+const BASH_PERMISSION_RULE: &str = "Bash(ccswitcher:*)";
+
+/// Runs the CLI and returns the process exit code. Errors go to stderr, or in
+/// JSON mode to stdout as the error envelope, never to both.
+pub fn run() -> i32 {
     let cli = Cli::parse();
-    run_with_cli(cli, &mut std::io::stdout())
+    let format = cli.output_format();
+    let mut stdout = std::io::stdout();
+    let Err(err) = run_with_cli(cli, &mut stdout) else {
+        return 0;
+    };
+    let reported = format == OutputFormat::Json
+        && report::write_json(&mut stdout, &report::error(&err)).is_ok();
+    if !reported {
+        eprintln!("Error: {err}");
+    }
+    err.exit_code()
 }
 
 fn run_with_cli(cli: Cli, out: &mut dyn Write) -> Result<()> {
     let paths = paths::AppPaths::resolve()?;
+    let format = cli.output_format();
 
     match cli.command {
         None => {
             let mut cfg = config::load(&paths)?;
             run_interactive_menu(&mut cfg, &paths, out)
         }
-        Some(Commands::Install) => install_slash_command(&paths, out),
+        Some(Commands::Install) => install_slash_command(&paths, format, out),
+        Some(Commands::Hook { shell }) => {
+            write!(out, "{}", shell.script()).map_err(AppError::output)
+        }
         Some(Commands::List) => {
             let cfg = config::load(&paths)?;
-            list_presets(&cfg, out)
+            list_presets(&cfg, format, out)
         }
         Some(Commands::Current) => {
             let cfg = config::load(&paths)?;
-            show_current(&cfg, out)
+            show_current(&cfg, &paths, format, out)
         }
+        Some(Commands::Doctor) => run_doctor(&paths, format, out),
+        Some(Commands::Providers) => list_providers(&paths, format, out),
+        Some(Commands::Allow { path }) => allow_marker(&paths, path, format, out),
+        Some(Commands::Deny { path }) => deny_marker(&paths, path, format, out),
+        Some(Commands::History { since }) => show_history(&paths, since, format, out),
         Some(other) => {
             let mut cfg = config::load(&paths)?;
             match other {
-                Commands::Use { preset } => use_preset(&mut cfg, &preset, &paths, out),
-                Commands::Add(args) => add_preset(&mut cfg, args, &paths, out),
-                Commands::Remove { preset } => remove_preset(&mut cfg, &preset, &paths, out),
-                Commands::ResetOfficial => reset_official(&mut cfg, &paths, out),
-                Commands::List | Commands::Current | Commands::Install => {
+                Commands::Use {
+                    preset,
+                    dry_run,
+                    scope,
+                    verify,
+                    force,
+                } => {
+                    if verify {
+                        check_preset(&cfg, &preset, &paths, format == OutputFormat::Json, out)?;
+                    }
+                    match (dry_run, scope) {
+                        (false, Scope::User) => use_preset(&mut cfg, &preset, &paths, format, out),
+                        (false, scope) => {
+                            use_preset_in_scope(&cfg, &preset, &paths, scope, force, format, out)
+                        }
+                        (true, scope) => {
+                            preview_use(&cfg, &preset, &paths.scoped(scope)?, format, out)
+                        }
+                    }
+                }
+                Commands::Test { preset } => test_preset(&cfg, &preset, &paths, format, out),
+                Commands::Exec { preset, command } => exec_preset(&cfg, &preset, &command, &paths),
+                Commands::Env {
+                    preset,
+                    shell,
+                    unset,
+                } => print_preset_env(&cfg, preset.as_deref(), shell, unset, &paths, format, out),
+                Commands::Auto {
+                    scope,
+                    quiet,
+                    force,
+                } => auto_switch(&mut cfg, &paths, scope, quiet, force, format, out),
+                Commands::Diff { preset } => diff_preset(&cfg, &preset, &paths, format, out),
+                Commands::Add(cli::AddCommand {
+                    from_current: Some(name),
+                    ..
+                }) => add_preset_from_current(&mut cfg, &name, &paths, format, out),
+                Commands::Add(cli::AddCommand {
+                    preset: Some(args), ..
+                }) => add_preset(&mut cfg, args, &paths, format, out),
+                Commands::Add(_) => {
+                    unreachable!("clap requires --from-current or the preset fields")
+                }
+                Commands::Edit(args) => edit_preset(&mut cfg, args, &paths, format, out),
+                Commands::Rename { old, new } => {
+                    rename_preset(&mut cfg, &old, &new, &paths, format, out)
+                }
+                Commands::Clone(args) => clone_preset(&mut cfg, args, &paths, format, out),
+                Commands::Export(args) => export_presets(&cfg, args, format, out),
+                Commands::Import(args) => import_presets(&mut cfg, args, &paths, format, out),
+                Commands::Remove { preset } => {
+                    remove_preset(&mut cfg, &preset, &paths, format, out)
+                }
+                Commands::ResetOfficial {
+                    dry_run: false,
+                    scope: Scope::User,
+                } => reset_official(&mut cfg, &paths, format, out),
+                Commands::ResetOfficial {
+                    dry_run: false,
+                    scope,
+                } => reset_scope(&paths, scope, format, out),
+                Commands::ResetOfficial {
+                    dry_run: true,
+                    scope,
+                } => preview_reset(&paths.scoped(scope)?, format, out),
+                Commands::Sync(args) => sync_preset(&mut cfg, args, &paths, format, out),
+                Commands::Undo => undo_switch(&mut cfg, &paths, format, out),
+                Commands::Redo => redo_switch(&mut cfg, &paths, format, out),
+                Commands::Backups { command } => {
+                    run_backups_command(&mut cfg, command, &paths, format, out)
+                }
+                Commands::Vault { command } => {
+                    run_vault_command(&mut cfg, command, &paths, format, out)
+                }
+                Commands::List
+                | Commands::Current
+                | Commands::Install
+                | Commands::Hook { .. }
+                | Commands::Allow { .. }
+                | Commands::Deny { .. }
+                | Commands::Providers
+                | Commands::Doctor
+                | Commands::History { .. } => {
                     unreachable!("handled above")
                 }
             }
@@ -52,7 +173,30 @@ fn run_with_cli(cli: Cli, out: &mut dyn Write) -> Result<()> {
     }
 }
 
-fn list_presets(cfg: &SwitcherConfig, out: &mut dyn Write) -> Result<()> {
+fn list_presets(cfg: &SwitcherConfig, format: OutputFormat, out: &mut dyn Write) -> Result<()> {
+    if format == OutputFormat::Json {
+        let presets: Vec<Value> = cfg
+            .presets
+            .iter()
+            .map(|(name, preset)| {
+                json!({
+                    "name": name,
+                    "provider": preset.provider.to_string(),
+                    "token_source": TokenSource::parse(&preset.auth_token).kind(),
+                    "active": cfg.active_preset.as_deref() == Some(name.as_str()),
+                })
+            })
+            .collect();
+        return report::write_json(
+            out,
+            &json!({
+                "ok": true,
+                "active_preset": cfg.active_preset,
+                "presets": presets,
+            }),
+        );
+    }
+
     if cfg.presets.is_empty() {
         writeln!(out, "No presets configured.").map_err(AppError::output)?;
         return Ok(());
@@ -67,18 +211,133 @@ fn list_presets(cfg: &SwitcherConfig, out: &mut dyn Write) -> Result<()> {
         };
         writeln!(
             out,
-            "{marker} {name} ({provider})",
-            provider = preset.provider
+            "{marker} {name} ({provider}, token: {source})",
+            provider = preset.provider,
+            source = TokenSource::parse(&preset.auth_token).kind()
         )
         .map_err(AppError::output)?;
     }
     Ok(())
 }
 
-fn show_current(cfg: &SwitcherConfig, out: &mut dyn Write) -> Result<()> {
+fn list_providers(
+    paths: &paths::AppPaths,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let catalog = provider::Catalog::load(paths)?;
+    let source = |kind: &ProviderKind| match kind {
+        ProviderKind::User(_) => "user",
+        _ => "built-in",
+    };
+    if format == OutputFormat::Json {
+        let providers: Vec<Value> = catalog
+            .entries()
+            .iter()
+            .map(|(kind, template)| {
+                json!({
+                    "name": kind.name(),
+                    "source": source(kind),
+                    "label": template.label,
+                    "base_url": template.base_url,
+                    "models": {
+                        "haiku_model": template.haiku_model,
+                        "sonnet_model": template.sonnet_model,
+                        "opus_model": template.opus_model,
+                    },
+                    "extra_env": template.extra_env,
+                    "required": template.required,
+                    "token_hint": template.token_hint,
+                })
+            })
+            .collect();
+        let warnings: Vec<String> = catalog.broken().iter().map(AppError::to_string).collect();
+        return report::write_json(
+            out,
+            &json!({ "ok": true, "providers": providers, "warnings": warnings }),
+        );
+    }
+
+    let shown = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_owned());
+    for (kind, template) in catalog.entries() {
+        let origin = match kind {
+            ProviderKind::User(_) => " (user template)",
+            _ => "",
+        };
+        writeln!(out, "{kind}: {}{origin}", template.label).map_err(AppError::output)?;
+        writeln!(out, "  base URL: {}", shown(&template.base_url)).map_err(AppError::output)?;
+        writeln!(
+            out,
+            "  models: haiku={} sonnet={} opus={}",
+            shown(&template.haiku_model),
+            shown(&template.sonnet_model),
+            shown(&template.opus_model)
+        )
+        .map_err(AppError::output)?;
+        for (key, value) in &template.extra_env {
+            writeln!(out, "  env {key}={value}").map_err(AppError::output)?;
+        }
+        if !template.required.is_empty() {
+            writeln!(out, "  required: {}", template.required.join(", "))
+                .map_err(AppError::output)?;
+        }
+        if let Some(hint) = &template.token_hint {
+            writeln!(out, "  token: {hint}").map_err(AppError::output)?;
+        }
+    }
+    for err in catalog.broken() {
+        writeln!(out, "Warning: {err}").map_err(AppError::output)?;
+    }
+    writeln!(
+        out,
+        "Use `ccswitcher add --provider <name> --name <preset> --auth-token <token>`; pass --base-url or model options to override."
+    )
+    .map_err(AppError::output)?;
+    writeln!(
+        out,
+        "Add your own providers as JSON files in {}.",
+        paths.providers_dir.display()
+    )
+    .map_err(AppError::output)
+}
+
+fn show_current(
+    cfg: &SwitcherConfig,
+    paths: &paths::AppPaths,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let scopes = project_scope_presets(paths)?;
+    let user_marker = state::load(paths)?.marker;
+    let cwd = std::env::current_dir().map_err(|err| AppError::io(".", err))?;
+    // A broken marker somewhere above must not hide the rest of the status.
+    let (marker_here, marker_error) = match marker::discover(&cwd) {
+        Ok(found) => (found, None),
+        Err(err @ AppError::InvalidMarker { .. }) => (None, Some(err.to_string())),
+        Err(err) => return Err(err),
+    };
     let Some(active_name) = cfg.active_preset.as_deref() else {
+        if format == OutputFormat::Json {
+            return report::write_json(
+                out,
+                &json!({
+                    "ok": true,
+                    "active_preset": null,
+                    "preset": null,
+                    "marker": null,
+                    "scopes": scopes_json(cfg, &scopes),
+                    "effective_preset": effective_preset(cfg, &scopes),
+                    "marker_here": marker_here_json(marker_here.as_ref()),
+                    "marker_error": marker_error,
+                }),
+            );
+        }
         writeln!(out, "No active preset.").map_err(AppError::output)?;
-        return Ok(());
+        write_scope_summary(cfg, &scopes, out)?;
+        if let Some(err) = &marker_error {
+            writeln!(out, "Warning: {err}").map_err(AppError::output)?;
+        }
+        return write_pending_marker(paths, &scopes, None, marker_here.as_ref(), out);
     };
 
     let preset = cfg
@@ -86,13 +345,495 @@ fn show_current(cfg: &SwitcherConfig, out: &mut dyn Write) -> Result<()> {
         .get(active_name)
         .ok_or_else(|| AppError::PresetNotFound(active_name.to_owned()))?;
 
-    write_preset_details(out, active_name, preset)
+    let drift = settings::drift(paths, &marker::as_applied(paths, active_name, preset))?;
+    if format == OutputFormat::Json {
+        return report::write_json(
+            out,
+            &json!({
+                "ok": true,
+                "active_preset": active_name,
+                "preset": report::masked_preset(preset),
+                "marker": user_marker,
+                "modified_externally": !drift.is_empty(),
+                "drifted_keys": drift.iter().map(settings::EnvChange::key).collect::<Vec<_>>(),
+                "scopes": scopes_json(cfg, &scopes),
+                "effective_preset": effective_preset(cfg, &scopes),
+                "marker_here": marker_here_json(marker_here.as_ref()),
+                "marker_error": marker_error,
+            }),
+        );
+    }
+    write_preset_details(out, active_name, preset)?;
+    if let Some(path) = &user_marker {
+        writeln!(out, "Chosen by marker file: {}", describe_marker(path))
+            .map_err(AppError::output)?;
+    }
+    if !drift.is_empty() {
+        writeln!(
+            out,
+            "Modified externally: settings.json env no longer matches this preset:"
+        )
+        .map_err(AppError::output)?;
+        write_env_changes(out, &drift)?;
+        writeln!(
+            out,
+            "Run `ccswitcher sync --reapply` to restore the preset, or `ccswitcher sync --capture` to keep the live values."
+        )
+        .map_err(AppError::output)?;
+    }
+    write_scope_summary(cfg, &scopes, out)?;
+    if let Some(err) = &marker_error {
+        writeln!(out, "Warning: {err}").map_err(AppError::output)?;
+    }
+    write_pending_marker(
+        paths,
+        &scopes,
+        user_marker.as_deref(),
+        marker_here.as_ref(),
+        out,
+    )
+}
+
+/// A marker path, noting when it also overrides preset fields.
+fn describe_marker(path: &std::path::Path) -> String {
+    match marker::read(path) {
+        Ok(found) if found.spec.has_overrides() => format!("{} (with overrides)", path.display()),
+        _ => path.display().to_string(),
+    }
+}
+
+fn marker_here_json(found: Option<&marker::Marker>) -> Value {
+    found.map_or(
+        Value::Null,
+        |found| json!({ "path": found.path, "preset": found.spec.preset }),
+    )
+}
+
+/// Points out a marker around the current directory that `auto` has not applied yet.
+fn write_pending_marker(
+    paths: &paths::AppPaths,
+    scopes: &[ScopePreset],
+    user_marker: Option<&std::path::Path>,
+    marker_here: Option<&marker::Marker>,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let Some(found) = marker_here else {
+        return Ok(());
+    };
+    let applied = std::iter::once(user_marker)
+        .chain(scopes.iter().map(|scope| scope.marker.as_deref()))
+        .any(|recorded| recorded == Some(found.path.as_path()));
+    if applied {
+        return Ok(());
+    }
+    let next_step = if marker::is_allowed(paths, found)? {
+        "run `ccswitcher auto` to apply it"
+    } else {
+        "review it, then run `ccswitcher allow` and `ccswitcher auto` to apply it"
+    };
+    writeln!(
+        out,
+        "Marker file {} selects preset '{}'; {next_step}.",
+        found.path.display(),
+        found.spec.preset
+    )
+    .map_err(AppError::output)
+}
+
+/// A project or local settings file around the current directory and the
+/// preset ccswitcher applied to it.
+struct ScopePreset {
+    scope: Scope,
+    settings_path: std::path::PathBuf,
+    preset: Option<String>,
+    marker: Option<std::path::PathBuf>,
+}
+
+/// Project and local scope presets, or nothing outside a project.
+fn project_scope_presets(paths: &paths::AppPaths) -> Result<Vec<ScopePreset>> {
+    let mut scopes = Vec::new();
+    for scope in [Scope::Project, Scope::Local] {
+        let Ok(scoped) = paths.scoped(scope) else {
+            break;
+        };
+        let scope_state = state::load(&scoped)?;
+        scopes.push(ScopePreset {
+            scope,
+            preset: scope_state.active_preset,
+            marker: scope_state.marker,
+            settings_path: scoped.settings_path,
+        });
+    }
+    Ok(scopes)
+}
+
+/// The preset Claude Code uses here: local wins over project, project over user.
+fn effective_preset<'a>(cfg: &'a SwitcherConfig, scopes: &'a [ScopePreset]) -> Option<&'a str> {
+    scopes
+        .iter()
+        .rev()
+        .find_map(|scope| scope.preset.as_deref())
+        .or(cfg.active_preset.as_deref())
+}
+
+fn scopes_json(cfg: &SwitcherConfig, scopes: &[ScopePreset]) -> Vec<Value> {
+    let mut entries = vec![json!({ "scope": "user", "preset": cfg.active_preset })];
+    entries.extend(scopes.iter().map(|scope| {
+        json!({
+            "scope": scope.scope.label(),
+            "preset": scope.preset,
+            "settings_path": scope.settings_path,
+            "marker": scope.marker,
+        })
+    }));
+    entries
+}
+
+fn write_scope_summary(
+    cfg: &SwitcherConfig,
+    scopes: &[ScopePreset],
+    out: &mut dyn Write,
+) -> Result<()> {
+    if scopes.iter().all(|scope| scope.preset.is_none()) {
+        return Ok(());
+    }
+    writeln!(out, "Scopes:").map_err(AppError::output)?;
+    writeln!(
+        out,
+        "- user: {}",
+        cfg.active_preset.as_deref().unwrap_or("none")
+    )
+    .map_err(AppError::output)?;
+    for scope in scopes {
+        let chosen_by = scope
+            .marker
+            .as_deref()
+            .map(|path| format!(", chosen by {}", describe_marker(path)))
+            .unwrap_or_default();
+        writeln!(
+            out,
+            "- {}: {} ({}{chosen_by})",
+            scope.scope.label(),
+            scope.preset.as_deref().unwrap_or("none"),
+            scope.settings_path.display()
+        )
+        .map_err(AppError::output)?;
+    }
+    writeln!(
+        out,
+        "Effective preset here: {}",
+        effective_preset(cfg, scopes).unwrap_or("none (official defaults)")
+    )
+    .map_err(AppError::output)?;
+    Ok(())
 }
 
 fn use_preset(
     cfg: &mut SwitcherConfig,
     preset_name: &str,
     paths: &paths::AppPaths,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let preset = cfg
+        .presets
+        .get(preset_name)
+        .ok_or_else(|| AppError::PresetNotFound(preset_name.to_owned()))?;
+    preset.validate_ready(preset_name)?;
+
+    let before = journal::capture(paths, cfg)?;
+    settings::apply_preset(paths, preset)?;
+    cfg.active_preset = Some(preset_name.to_owned());
+    config::save(paths, cfg)?;
+    let after = journal::capture(paths, cfg)?;
+    journal::record(paths, format!("use {preset_name}"), before, after)?;
+    history::append(
+        paths,
+        &history::HistoryRecord::new("use", Some(preset_name), Some(preset)),
+    )?;
+
+    if format == OutputFormat::Json {
+        return report::write_json(out, &report::ok("use", json!({ "preset": preset_name })));
+    }
+    writeln!(
+        out,
+        "Switched to preset '{preset_name}'. New requests will use this model configuration."
+    )
+    .map_err(AppError::output)?;
+    Ok(())
+}
+
+/// Applies a preset to a project or local settings file, leaving the
+/// user-level active preset and the undo journal untouched.
+fn use_preset_in_scope(
+    cfg: &SwitcherConfig,
+    preset_name: &str,
+    paths: &paths::AppPaths,
+    scope: Scope,
+    force: bool,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let preset = cfg
+        .presets
+        .get(preset_name)
+        .ok_or_else(|| AppError::PresetNotFound(preset_name.to_owned()))?;
+    preset.validate_ready(preset_name)?;
+
+    let scoped = paths.scoped(scope)?;
+    if scope == Scope::Project && !force {
+        return Err(AppError::ProjectScopeNeedsForce {
+            path: scoped.settings_path,
+        });
+    }
+    settings::apply_preset(&scoped, preset)?;
+    set_scope_preset(&scoped, Some(preset_name.to_owned()))?;
+    history::append(
+        paths,
+        &history::HistoryRecord::new("use", Some(preset_name), Some(preset)).in_scope(scope),
+    )?;
+
+    if format == OutputFormat::Json {
+        return report::write_json(
+            out,
+            &report::ok(
+                "use",
+                json!({
+                    "preset": preset_name,
+                    "scope": scope.label(),
+                    "settings_path": scoped.settings_path,
+                }),
+            ),
+        );
+    }
+    writeln!(
+        out,
+        "Switched {} scope to preset '{preset_name}' in {}.",
+        scope.label(),
+        scoped.settings_path.display()
+    )
+    .map_err(AppError::output)?;
+    if scope == Scope::Project {
+        writeln!(
+            out,
+            "Warning: project settings are usually committed and now contain the auth token; prefer `--scope local`."
+        )
+        .map_err(AppError::output)?;
+    }
+    Ok(())
+}
+
+fn set_scope_preset(scoped: &paths::AppPaths, preset: Option<String>) -> Result<()> {
+    let mut scope_state = state::load(scoped)?;
+    scope_state.active_preset = preset;
+    state::save(scoped, &scope_state)
+}
+
+/// Applies the preset chosen by the nearest `.ccswitcher` file. Nothing is
+/// written when that preset is already applied unchanged, so the `cd` hook
+/// does not pile up backups and journal entries.
+fn auto_switch(
+    cfg: &mut SwitcherConfig,
+    paths: &paths::AppPaths,
+    scope: Option<Scope>,
+    quiet: bool,
+    force: bool,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let cwd = std::env::current_dir().map_err(|err| AppError::io(".", err))?;
+    let Some(marker) = marker::discover(&cwd)? else {
+        if format == OutputFormat::Json {
+            return report::write_json(
+                out,
+                &report::ok("auto", json!({ "marker": null, "changed": false })),
+            );
+        }
+        if !quiet {
+            writeln!(
+                out,
+                "No {} file found in {} or its parents.",
+                marker::MARKER_FILE,
+                cwd.display()
+            )
+            .map_err(AppError::output)?;
+        }
+        return Ok(());
+    };
+
+    if !marker::is_allowed(paths, &marker)? {
+        return Err(AppError::MarkerNotAllowed { path: marker.path });
+    }
+
+    let name = marker.spec.preset.clone();
+    let scope = scope.or(marker.spec.scope).unwrap_or_default();
+    let preset = marker.resolve(cfg)?;
+    preset.validate_ready(&name)?;
+
+    let scoped = paths.scoped(scope)?;
+    let scope_state = state::load(&scoped)?;
+    let applied = match scope {
+        Scope::User => cfg.active_preset.as_deref(),
+        _ => scope_state.active_preset.as_deref(),
+    };
+    let unchanged = applied == Some(name.as_str())
+        && scope_state.marker.as_deref() == Some(marker.path.as_path())
+        && settings::drift(&scoped, &preset)?.is_empty();
+
+    // A marker can ask for the project scope, but only the user can agree to
+    // putting the token into a committed file.
+    if !unchanged && scope == Scope::Project && !force {
+        return Err(AppError::ProjectScopeNeedsForce {
+            path: scoped.settings_path,
+        });
+    }
+    if !unchanged {
+        if scope == Scope::User {
+            let before = journal::capture(paths, cfg)?;
+            settings::apply_preset(paths, &preset)?;
+            cfg.active_preset = Some(name.clone());
+            config::save(paths, cfg)?;
+            marker::record(paths, Some(marker.path.clone()))?;
+            let after = journal::capture(paths, cfg)?;
+            journal::record(paths, format!("auto {name}"), before, after)?;
+        } else {
+            settings::apply_preset(&scoped, &preset)?;
+            set_scope_preset(&scoped, Some(name.clone()))?;
+            marker::record(&scoped, Some(marker.path.clone()))?;
+        }
+        history::append(
+            paths,
+            &history::HistoryRecord::new("auto", Some(&name), Some(&preset)).in_scope(scope),
+        )?;
+    }
+
+    if format == OutputFormat::Json {
+        return report::write_json(
+            out,
+            &report::ok(
+                "auto",
+                json!({
+                    "preset": name,
+                    "scope": scope.label(),
+                    "marker": marker.path,
+                    "settings_path": scoped.settings_path,
+                    "changed": !unchanged,
+                }),
+            ),
+        );
+    }
+    if unchanged && quiet {
+        return Ok(());
+    }
+    let action = if unchanged {
+        "Already using"
+    } else {
+        "Switched to"
+    };
+    writeln!(
+        out,
+        "{action} preset '{name}' in {} scope, chosen by {}.",
+        scope.label(),
+        marker.path.display()
+    )
+    .map_err(AppError::output)
+}
+
+/// The marker file `allow` and `deny` act on: `path` itself, the marker in
+/// directory `path`, or the nearest one from the current directory.
+fn locate_marker(path: Option<PathBuf>) -> Result<PathBuf> {
+    let start = match path {
+        Some(path) if path.is_dir() => path.join(marker::MARKER_FILE),
+        Some(path) => return Ok(path),
+        None => std::env::current_dir().map_err(|err| AppError::io(".", err))?,
+    };
+    if start.is_file() {
+        return Ok(start);
+    }
+    start
+        .ancestors()
+        .map(|dir| dir.join(marker::MARKER_FILE))
+        .find(|candidate| candidate.is_file())
+        .ok_or_else(|| {
+            AppError::io(
+                &start,
+                std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("no {} file found here or in a parent", marker::MARKER_FILE),
+                ),
+            )
+        })
+}
+
+fn allow_marker(
+    paths: &paths::AppPaths,
+    path: Option<PathBuf>,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let found = marker::read(&locate_marker(path)?)?;
+    marker::allow(paths, &found)?;
+    let sensitive = found.spec.sensitive_fields();
+
+    if format == OutputFormat::Json {
+        return report::write_json(
+            out,
+            &report::ok(
+                "allow",
+                json!({
+                    "marker": found.path,
+                    "preset": found.spec.preset,
+                    "sensitive_fields": sensitive,
+                }),
+            ),
+        );
+    }
+    writeln!(
+        out,
+        "Allowed {} (preset '{}'). `ccswitcher auto` will apply it until the file changes.",
+        found.path.display(),
+        found.spec.preset
+    )
+    .map_err(AppError::output)?;
+    if !sensitive.is_empty() {
+        writeln!(
+            out,
+            "Note: it sets {}, which decide where the auth token is sent or written.",
+            sensitive.join(", ")
+        )
+        .map_err(AppError::output)?;
+    }
+    Ok(())
+}
+
+fn deny_marker(
+    paths: &paths::AppPaths,
+    path: Option<PathBuf>,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let path = locate_marker(path)?;
+    let removed = marker::deny(paths, &path)?;
+
+    if format == OutputFormat::Json {
+        return report::write_json(
+            out,
+            &report::ok("deny", json!({ "marker": path, "removed": removed })),
+        );
+    }
+    let message = if removed {
+        "Withdrew the approval of"
+    } else {
+        "No approval to withdraw for"
+    };
+    writeln!(out, "{message} {}.", path.display()).map_err(AppError::output)
+}
+
+fn preview_use(
+    cfg: &SwitcherConfig,
+    preset_name: &str,
+    paths: &paths::AppPaths,
+    format: OutputFormat,
     out: &mut dyn Write,
 ) -> Result<()> {
     let preset = cfg
@@ -101,91 +842,1258 @@ fn use_preset(
         .ok_or_else(|| AppError::PresetNotFound(preset_name.to_owned()))?;
     preset.validate_ready(preset_name)?;
 
-    settings::apply_preset(paths, preset)?;
-    cfg.active_preset = Some(preset_name.to_owned());
-    config::save(paths, cfg)?;
+    let pending = settings::plan_apply(paths, preset)?;
+    let changes = settings::pending_changes(paths, &pending)?;
+    if format == OutputFormat::Json {
+        return report::write_json(
+            out,
+            &report::ok(
+                "use",
+                json!({
+                    "preset": preset_name,
+                    "dry_run": true,
+                    "changes": env_changes_json(&changes),
+                }),
+            ),
+        );
+    }
+    writeln!(
+        out,
+        "Dry run: switching to preset '{preset_name}' would change settings.json env:"
+    )
+    .map_err(AppError::output)?;
+    write_change_list(out, &changes)
+}
+
+fn test_preset(
+    cfg: &SwitcherConfig,
+    preset_name: &str,
+    paths: &paths::AppPaths,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let result = check_preset(cfg, preset_name, paths, format == OutputFormat::Json, out)?;
+    if format == OutputFormat::Json {
+        return report::write_json(out, &report::ok("test", result));
+    }
+    Ok(())
+}
+
+/// Sends the connectivity check requests for a preset, printing one line per
+/// request unless `quiet`. Returns the JSON result, which a failure carries too.
+fn check_preset(
+    cfg: &SwitcherConfig,
+    preset_name: &str,
+    paths: &paths::AppPaths,
+    quiet: bool,
+    out: &mut dyn Write,
+) -> Result<Value> {
+    let preset = cfg
+        .presets
+        .get(preset_name)
+        .ok_or_else(|| AppError::PresetNotFound(preset_name.to_owned()))?;
+    preset.validate_ready(preset_name)?;
+
+    let probes = probe::run(paths, preset)?;
+    if !quiet {
+        writeln!(
+            out,
+            "Testing preset '{preset_name}' at {}:",
+            probe::messages_url(&preset.base_url)
+        )
+        .map_err(AppError::output)?;
+        for probe in &probes {
+            let status = if probe.passed() { "pass" } else { "fail" };
+            let response = match probe.status {
+                Some(code) => format!("HTTP {code} in {} ms", probe.latency_ms),
+                None => format!("no response after {} ms", probe.latency_ms),
+            };
+            let detail = probe
+                .error
+                .as_deref()
+                .map(|error| format!(": {error}"))
+                .unwrap_or_default();
+            writeln!(out, "[{status}] {}: {response}{detail}", probe.label())
+                .map_err(AppError::output)?;
+        }
+    }
+
+    let failures: Vec<String> = probes
+        .iter()
+        .filter(|probe| !probe.passed())
+        .map(|probe| match probe.status {
+            Some(code) => format!("{} returned HTTP {code}", probe.label()),
+            None => format!("{} got no response", probe.label()),
+        })
+        .collect();
+    if !quiet {
+        writeln!(
+            out,
+            "{} passed, {} failure(s).",
+            probes.len() - failures.len(),
+            failures.len()
+        )
+        .map_err(AppError::output)?;
+    }
+    let result = json!({
+        "preset": preset_name,
+        "url": probe::messages_url(&preset.base_url),
+        "results": probes,
+    });
+    if !failures.is_empty() {
+        // Written by `run` as part of the error envelope.
+        return Err(AppError::ConnectivityCheckFailed {
+            preset: preset_name.to_owned(),
+            failures: failures.join("; "),
+            report: result,
+        });
+    }
+    Ok(result)
+}
+
+/// Runs `command` with the preset's env, leaving settings.json and the active preset as they are.
+fn exec_preset(
+    cfg: &SwitcherConfig,
+    preset_name: &str,
+    command: &[String],
+    paths: &paths::AppPaths,
+) -> Result<()> {
+    let preset = cfg
+        .presets
+        .get(preset_name)
+        .ok_or_else(|| AppError::PresetNotFound(preset_name.to_owned()))?;
+    preset.validate_ready(preset_name)?;
+
+    let env = settings::resolved_env(paths, preset)?;
+    history::append(
+        paths,
+        &history::HistoryRecord::new("exec", Some(preset_name), Some(preset)),
+    )?;
+    exec::replace_process(command, &env)
+}
+
+/// Prints the env `use` would write as `shell` lines, or with `unset` the lines
+/// that clear it again. The auth token is resolved, so the output is a secret.
+fn print_preset_env(
+    cfg: &SwitcherConfig,
+    preset_name: Option<&str>,
+    shell: shell::Shell,
+    unset: bool,
+    paths: &paths::AppPaths,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let preset = preset_name
+        .map(|name| {
+            cfg.presets
+                .get(name)
+                .ok_or_else(|| AppError::PresetNotFound(name.to_owned()))
+        })
+        .transpose()?;
+
+    if let (Some(name), Some(preset)) = (preset_name, preset) {
+        // Keys are printed unquoted, so anything but a plain name could inject shell code.
+        preset.validate_env_keys(name)?;
+    }
+
+    if unset {
+        let mut keys: Vec<&str> = OVERRIDE_ENV_KEYS.to_vec();
+        keys.extend(
+            preset
+                .iter()
+                .flat_map(|preset| preset.extra_env.keys().map(String::as_str)),
+        );
+        if format == OutputFormat::Json {
+            return report::write_json(
+                out,
+                &report::ok(
+                    "env",
+                    json!({ "preset": preset_name, "shell": shell.label(), "unset": keys }),
+                ),
+            );
+        }
+        for line in keys.into_iter().filter_map(|key| shell.unset_line(key)) {
+            writeln!(out, "{line}").map_err(AppError::output)?;
+        }
+        return Ok(());
+    }
+
+    let (Some(preset_name), Some(preset)) = (preset_name, preset) else {
+        unreachable!("clap requires a preset unless --unset is given")
+    };
+    preset.validate_ready(preset_name)?;
+    let env = settings::resolved_env(paths, preset)?;
+    if format == OutputFormat::Json {
+        return report::write_json(
+            out,
+            &report::ok(
+                "env",
+                json!({ "preset": preset_name, "shell": shell.label(), "env": env }),
+            ),
+        );
+    }
+    for (key, value) in &env {
+        writeln!(out, "{}", shell.set_line(key, value)).map_err(AppError::output)?;
+    }
+    Ok(())
+}
+
+fn diff_preset(
+    cfg: &SwitcherConfig,
+    preset_name: &str,
+    paths: &paths::AppPaths,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let preset = cfg
+        .presets
+        .get(preset_name)
+        .ok_or_else(|| AppError::PresetNotFound(preset_name.to_owned()))?;
+
+    let pending = settings::plan_apply(paths, preset)?;
+    let changes = settings::pending_changes(paths, &pending)?;
+    if format == OutputFormat::Json {
+        return report::write_json(
+            out,
+            &report::ok(
+                "diff",
+                json!({ "preset": preset_name, "changes": env_changes_json(&changes) }),
+            ),
+        );
+    }
+    writeln!(
+        out,
+        "Differences between live settings.json env and preset '{preset_name}':"
+    )
+    .map_err(AppError::output)?;
+    write_change_list(out, &changes)
+}
+
+fn preview_reset(paths: &paths::AppPaths, format: OutputFormat, out: &mut dyn Write) -> Result<()> {
+    let pending = settings::plan_reset(paths)?;
+    let changes = settings::pending_changes(paths, &pending)?;
+    if format == OutputFormat::Json {
+        return report::write_json(
+            out,
+            &report::ok(
+                "reset-official",
+                json!({ "dry_run": true, "changes": env_changes_json(&changes) }),
+            ),
+        );
+    }
+    writeln!(
+        out,
+        "Dry run: resetting to official defaults would change settings.json env:"
+    )
+    .map_err(AppError::output)?;
+    write_change_list(out, &changes)
+}
+
+/// Env changes as JSON, with credential-like values masked as in text output.
+fn env_changes_json(changes: &[settings::EnvChange]) -> Vec<Value> {
+    let shown = |key: &str, value: &Value| settings::display_env_value(key, value);
+    changes
+        .iter()
+        .map(|change| match change {
+            settings::EnvChange::Added { key, new } => {
+                json!({ "key": key, "change": "added", "new": shown(key, new) })
+            }
+            settings::EnvChange::Changed { key, old, new } => json!({
+                "key": key,
+                "change": "changed",
+                "old": shown(key, old),
+                "new": shown(key, new),
+            }),
+            settings::EnvChange::Removed { key, old } => {
+                json!({ "key": key, "change": "removed", "old": shown(key, old) })
+            }
+        })
+        .collect()
+}
+
+fn write_change_list(out: &mut dyn Write, changes: &[settings::EnvChange]) -> Result<()> {
+    if changes.is_empty() {
+        writeln!(out, "  (no changes)").map_err(AppError::output)?;
+        return Ok(());
+    }
+    write_env_changes(out, changes)
+}
+
+fn write_env_changes(out: &mut dyn Write, changes: &[settings::EnvChange]) -> Result<()> {
+    for change in changes {
+        match change {
+            settings::EnvChange::Added { key, new } => {
+                writeln!(out, "  + {key} = {}", settings::display_env_value(key, new))
+            }
+            settings::EnvChange::Changed { key, old, new } => writeln!(
+                out,
+                "  ~ {key}: {} -> {}",
+                settings::display_env_value(key, old),
+                settings::display_env_value(key, new)
+            ),
+            settings::EnvChange::Removed { key, old } => writeln!(
+                out,
+                "  - {key} (was {})",
+                settings::display_env_value(key, old)
+            ),
+        }
+        .map_err(AppError::output)?;
+    }
+    Ok(())
+}
+
+fn add_preset(
+    cfg: &mut SwitcherConfig,
+    args: cli::AddArgs,
+    paths: &paths::AppPaths,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let template = provider::template(paths, &args.provider)?;
+    let (name, preset) = args.into_name_and_preset(template.as_ref())?;
+    save_new_preset(cfg, name, preset, paths, format, out)
+}
+
+/// Rejects an `edit`/`clone` provider tag that is not in the catalog.
+fn ensure_known_provider(overrides: &cli::PresetOverrides, paths: &paths::AppPaths) -> Result<()> {
+    if let Some(kind) = &overrides.provider {
+        provider::template(paths, kind)?;
+    }
+    Ok(())
+}
+
+/// Saves the managed env keys currently in `settings.json` as a new preset.
+fn add_preset_from_current(
+    cfg: &mut SwitcherConfig,
+    name: &str,
+    paths: &paths::AppPaths,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let preset = settings::preset_from_live(paths)?;
+    let name = name.trim().to_owned();
+    save_new_preset(cfg, name.clone(), preset, paths, format, out)?;
+    if format == OutputFormat::Text {
+        writeln!(
+            out,
+            "Captured from {}. Run `ccswitcher use {name}` to manage it from now on.",
+            paths.settings_path.display()
+        )
+        .map_err(AppError::output)?;
+    }
+    Ok(())
+}
+
+fn save_new_preset(
+    cfg: &mut SwitcherConfig,
+    name: String,
+    preset: Preset,
+    paths: &paths::AppPaths,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    if cfg.presets.contains_key(&name) {
+        return Err(AppError::PresetAlreadyExists(name));
+    }
+    preset.validate_ready(&name)?;
+
+    let record = history::HistoryRecord::new("add", Some(&name), Some(&preset));
+    let auth_token = preset.auth_token.clone();
+    cfg.presets.insert(name.clone(), preset);
+    config::save(paths, cfg)?;
+    history::append(paths, &record)?;
+
+    if format == OutputFormat::Json {
+        return report::write_json(out, &report::ok("add", json!({ "preset": name })));
+    }
+    writeln!(out, "Saved preset '{name}'.").map_err(AppError::output)?;
+    if matches!(TokenSource::parse(&auth_token), TokenSource::Plain(_)) {
+        writeln!(out, "Warning: this preset stores auth_token in plain text.")
+            .map_err(AppError::output)?;
+        if vault::exists(paths) {
+            writeln!(
+                out,
+                "Run `ccswitcher vault migrate` to move it into the vault."
+            )
+            .map_err(AppError::output)?;
+        }
+    }
+    Ok(())
+}
+
+fn edit_preset(
+    cfg: &mut SwitcherConfig,
+    args: cli::EditArgs,
+    paths: &paths::AppPaths,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let name = args.preset.trim().to_owned();
+    let mut preset = cfg
+        .presets
+        .get(&name)
+        .cloned()
+        .ok_or_else(|| AppError::PresetNotFound(name.clone()))?;
+
+    if !args.overrides.has_changes() {
+        if format == OutputFormat::Json {
+            return report::write_json(
+                out,
+                &report::ok(
+                    "edit",
+                    json!({ "preset": name, "changed": false, "reapplied": false }),
+                ),
+            );
+        }
+        writeln!(out, "No changes given for preset '{name}'.").map_err(AppError::output)?;
+        return Ok(());
+    }
+
+    ensure_known_provider(&args.overrides, paths)?;
+    args.overrides.apply_to(&mut preset);
+    preset.validate_ready(&name)?;
+
+    let is_active = cfg.active_preset.as_deref() == Some(name.as_str());
+    let before = if is_active {
+        let before = journal::capture(paths, cfg)?;
+        settings::apply_preset(paths, &preset)?;
+        Some(before)
+    } else {
+        None
+    };
+    cfg.presets.insert(name.clone(), preset);
+    config::save(paths, cfg)?;
+    if let Some(before) = before {
+        let after = journal::capture(paths, cfg)?;
+        journal::record(paths, format!("edit {name}"), before, after)?;
+    }
+
+    if format == OutputFormat::Json {
+        return report::write_json(
+            out,
+            &report::ok(
+                "edit",
+                json!({ "preset": name, "changed": true, "reapplied": is_active }),
+            ),
+        );
+    }
+    writeln!(out, "Updated preset '{name}'.").map_err(AppError::output)?;
+    if is_active {
+        writeln!(
+            out,
+            "Re-applied active preset '{name}'. New requests will use the updated configuration."
+        )
+        .map_err(AppError::output)?;
+    }
+    Ok(())
+}
+
+fn sync_preset(
+    cfg: &mut SwitcherConfig,
+    args: cli::SyncArgs,
+    paths: &paths::AppPaths,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let Some(name) = cfg.active_preset.clone() else {
+        if format == OutputFormat::Json {
+            return report::write_json(
+                out,
+                &report::ok("sync", json!({ "active": null, "changed": false })),
+            );
+        }
+        writeln!(out, "No active preset.").map_err(AppError::output)?;
+        return Ok(());
+    };
+    let mut preset = cfg
+        .presets
+        .get(&name)
+        .cloned()
+        .ok_or_else(|| AppError::PresetNotFound(name.clone()))?;
+    preset.validate_env_keys(&name)?;
+    let applied = marker::as_applied(paths, &name, &preset);
+    let drift = settings::drift(paths, &applied)?;
+    let keys: Vec<&str> = drift.iter().map(settings::EnvChange::key).collect();
+    let mode = if args.capture { "capture" } else { "reapply" };
+
+    if !drift.is_empty() && args.capture {
+        settings::capture_drift(&mut preset, &drift);
+        preset.validate_ready(&name)?;
+        cfg.presets.insert(name.clone(), preset);
+        config::save(paths, cfg)?;
+    } else if !drift.is_empty() {
+        let before = journal::capture(paths, cfg)?;
+        let recorded = state::load(paths)?.marker;
+        settings::apply_preset(paths, &applied)?;
+        marker::record(paths, recorded)?;
+        let after = journal::capture(paths, cfg)?;
+        journal::record(paths, format!("sync {name}"), before, after)?;
+    }
+    if !drift.is_empty() {
+        let preset = cfg.presets.get(&name);
+        history::append(
+            paths,
+            &history::HistoryRecord::new("sync", Some(&name), preset),
+        )?;
+    }
+
+    if format == OutputFormat::Json {
+        return report::write_json(
+            out,
+            &report::ok(
+                "sync",
+                json!({ "preset": name, "mode": mode, "keys": keys }),
+            ),
+        );
+    }
+    if drift.is_empty() {
+        writeln!(
+            out,
+            "Preset '{name}' matches settings.json; nothing to sync."
+        )
+    } else if args.capture {
+        writeln!(out, "Captured {} into preset '{name}'.", keys.join(", "))
+    } else {
+        writeln!(
+            out,
+            "Re-applied preset '{name}'; restored {}.",
+            keys.join(", ")
+        )
+    }
+    .map_err(AppError::output)
+}
+
+fn rename_preset(
+    cfg: &mut SwitcherConfig,
+    old_name: &str,
+    new_name: &str,
+    paths: &paths::AppPaths,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    if cfg.presets.contains_key(new_name) {
+        return Err(AppError::PresetAlreadyExists(new_name.to_owned()));
+    }
+    if !cfg.presets.contains_key(old_name) {
+        return Err(AppError::PresetNotFound(old_name.to_owned()));
+    }
+    // Marker files live in other people's checkouts; they are not rewritten.
+    if let Some(path) = marker::approved_for(paths, old_name)?.into_iter().next() {
+        return Err(AppError::PresetNamedByMarker {
+            preset: old_name.to_owned(),
+            path,
+        });
+    }
+
+    let preset = cfg
+        .presets
+        .remove(old_name)
+        .ok_or_else(|| AppError::PresetNotFound(old_name.to_owned()))?;
+    cfg.presets.insert(new_name.to_owned(), preset);
+    let was_active = cfg.active_preset.as_deref() == Some(old_name);
+    if was_active {
+        cfg.active_preset = Some(new_name.to_owned());
+    }
+    config::save(paths, cfg)?;
+    journal::rename_preset(paths, old_name, new_name)?;
+    for scope_paths in paths.recorded_scopes()? {
+        state::rename_preset(&scope_paths, old_name, new_name)?;
+        journal::rename_preset(&scope_paths, old_name, new_name)?;
+    }
+
+    if format == OutputFormat::Json {
+        return report::write_json(
+            out,
+            &report::ok(
+                "rename",
+                json!({ "from": old_name, "to": new_name, "active": was_active }),
+            ),
+        );
+    }
+    writeln!(out, "Renamed preset '{old_name}' to '{new_name}'.").map_err(AppError::output)?;
+    if was_active {
+        writeln!(out, "Active preset is now '{new_name}'.").map_err(AppError::output)?;
+    }
+    Ok(())
+}
+
+fn clone_preset(
+    cfg: &mut SwitcherConfig,
+    args: cli::CloneArgs,
+    paths: &paths::AppPaths,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let source = args.source.trim().to_owned();
+    let target = args.target.trim().to_owned();
+    if cfg.presets.contains_key(&target) {
+        return Err(AppError::PresetAlreadyExists(target));
+    }
+    let mut preset = cfg
+        .presets
+        .get(&source)
+        .cloned()
+        .ok_or_else(|| AppError::PresetNotFound(source.clone()))?;
+
+    ensure_known_provider(&args.overrides, paths)?;
+    args.overrides.apply_to(&mut preset);
+    preset.validate_ready(&target)?;
+
+    cfg.presets.insert(target.clone(), preset);
+    config::save(paths, cfg)?;
+
+    if format == OutputFormat::Json {
+        return report::write_json(
+            out,
+            &report::ok("clone", json!({ "from": source, "to": target })),
+        );
+    }
+    writeln!(out, "Cloned preset '{source}' to '{target}'.").map_err(AppError::output)?;
+    Ok(())
+}
+
+fn remove_preset(
+    cfg: &mut SwitcherConfig,
+    preset_name: &str,
+    paths: &paths::AppPaths,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    if cfg.active_preset.as_deref() == Some(preset_name) {
+        return Err(AppError::CannotRemoveActivePreset(preset_name.to_owned()));
+    }
+
+    let Some(removed) = cfg.presets.remove(preset_name) else {
+        return Err(AppError::PresetNotFound(preset_name.to_owned()));
+    };
+
+    config::save(paths, cfg)?;
+    history::append(
+        paths,
+        &history::HistoryRecord::new("remove", Some(preset_name), Some(&removed)),
+    )?;
+    if format == OutputFormat::Json {
+        return report::write_json(out, &report::ok("remove", json!({ "preset": preset_name })));
+    }
+    writeln!(out, "Removed preset '{preset_name}'.").map_err(AppError::output)?;
+    Ok(())
+}
+
+fn export_presets(
+    cfg: &SwitcherConfig,
+    args: cli::ExportArgs,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let bundle = bundle::build(cfg, &args.presets, args.include_tokens)?;
+    bundle::write(&args.out, &bundle)?;
+
+    if format == OutputFormat::Json {
+        let presets: Vec<&String> = bundle.presets.keys().collect();
+        return report::write_json(
+            out,
+            &report::ok(
+                "export",
+                json!({
+                    "file": args.out,
+                    "presets": presets,
+                    "include_tokens": args.include_tokens,
+                }),
+            ),
+        );
+    }
+
+    writeln!(
+        out,
+        "Exported {} preset(s) to {}.",
+        bundle.presets.len(),
+        args.out.display()
+    )
+    .map_err(AppError::output)?;
+    if args.include_tokens {
+        writeln!(
+            out,
+            "Warning: this bundle contains auth tokens in plain text."
+        )
+        .map_err(AppError::output)?;
+    } else {
+        writeln!(out, "Auth tokens were stripped from the bundle.").map_err(AppError::output)?;
+    }
+    Ok(())
+}
+
+fn import_presets(
+    cfg: &mut SwitcherConfig,
+    args: cli::ImportArgs,
+    paths: &paths::AppPaths,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let incoming = bundle::read(&args.file)?;
+    bundle::check_token_sources(&incoming, args.allow_token_commands)?;
+    // Imported presets are only stored; applying one stays an explicit `use`,
+    // even when the active preset was overwritten.
+    let outcomes = bundle::merge(cfg, incoming, args.on_conflict);
+    config::save(paths, cfg)?;
+    let is_active = |name: &str| cfg.active_preset.as_deref() == Some(name);
+
+    let missing_token = |name: &str| {
+        cfg.presets
+            .get(name)
+            .is_some_and(|preset| preset.auth_token.trim().is_empty())
+    };
+    if format == OutputFormat::Json {
+        let presets: Vec<Value> = outcomes
+            .iter()
+            .map(|outcome| match outcome {
+                bundle::ImportOutcome::Added(name) => {
+                    json!({ "name": name, "action": "added", "missing_token": missing_token(name) })
+                }
+                bundle::ImportOutcome::Overwritten(name) => json!({
+                    "name": name,
+                    "action": "overwritten",
+                    "missing_token": missing_token(name),
+                    "needs_use": is_active(name),
+                }),
+                bundle::ImportOutcome::Renamed { from, to } => json!({
+                    "name": to,
+                    "from": from,
+                    "action": "renamed",
+                    "missing_token": missing_token(to),
+                }),
+                bundle::ImportOutcome::Skipped(name) => {
+                    json!({ "name": name, "action": "skipped" })
+                }
+            })
+            .collect();
+        return report::write_json(out, &report::ok("import", json!({ "presets": presets })));
+    }
+    for outcome in &outcomes {
+        let (line, imported_name) = match outcome {
+            bundle::ImportOutcome::Added(name) => (format!("Imported preset '{name}'."), name),
+            bundle::ImportOutcome::Overwritten(name) => {
+                (format!("Overwrote preset '{name}'."), name)
+            }
+            bundle::ImportOutcome::Renamed { from, to } => {
+                (format!("Imported preset '{from}' as '{to}'."), to)
+            }
+            bundle::ImportOutcome::Skipped(name) => {
+                writeln!(out, "Skipped preset '{name}' (already exists).")
+                    .map_err(AppError::output)?;
+                continue;
+            }
+        };
+        writeln!(out, "{line}").map_err(AppError::output)?;
+        if missing_token(imported_name) {
+            writeln!(
+                out,
+                "Note: preset '{imported_name}' has no auth token. Set one with `ccswitcher edit {imported_name} --auth-token <token>`."
+            )
+            .map_err(AppError::output)?;
+        }
+        if matches!(outcome, bundle::ImportOutcome::Overwritten(_)) && is_active(imported_name) {
+            writeln!(
+                out,
+                "Note: '{imported_name}' is the active preset. Review it, then run `ccswitcher use {imported_name}` to apply the imported settings."
+            )
+            .map_err(AppError::output)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_preset_details(out: &mut dyn Write, name: &str, preset: &Preset) -> Result<()> {
+    writeln!(out, "Active preset: {name}").map_err(AppError::output)?;
+    writeln!(out, "Provider: {}", preset.provider).map_err(AppError::output)?;
+    writeln!(out, "Base URL: {}", preset.base_url).map_err(AppError::output)?;
+    writeln!(
+        out,
+        "Auth token: {}",
+        TokenSource::parse(&preset.auth_token).describe()
+    )
+    .map_err(AppError::output)?;
+    writeln!(out, "Haiku model: {}", preset.models.haiku_model).map_err(AppError::output)?;
+    writeln!(out, "Sonnet model: {}", preset.models.sonnet_model).map_err(AppError::output)?;
+    writeln!(out, "Opus model: {}", preset.models.opus_model).map_err(AppError::output)?;
+    for (key, value) in &preset.extra_env {
+        let value = settings::display_env_value(key, &Value::String(value.clone()));
+        writeln!(out, "Env {key}: {value}").map_err(AppError::output)?;
+    }
+    Ok(())
+}
+
+fn reset_official(
+    cfg: &mut SwitcherConfig,
+    paths: &paths::AppPaths,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let before = journal::capture(paths, cfg)?;
+    settings::reset_to_official(paths)?;
+    cfg.active_preset = None;
+    config::save(paths, cfg)?;
+    let after = journal::capture(paths, cfg)?;
+    journal::record(paths, "reset-official".to_owned(), before, after)?;
+    history::append(
+        paths,
+        &history::HistoryRecord::new("reset-official", None, None),
+    )?;
+    if format == OutputFormat::Json {
+        return report::write_json(out, &report::ok("reset-official", json!({})));
+    }
+    writeln!(
+        out,
+        "Reset complete. Official Claude model/provider defaults will be used for new requests."
+    )
+    .map_err(AppError::output)?;
+    Ok(())
+}
+
+fn reset_scope(
+    paths: &paths::AppPaths,
+    scope: Scope,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let scoped = paths.scoped(scope)?;
+    settings::reset_to_official(&scoped)?;
+    set_scope_preset(&scoped, None)?;
+    history::append(
+        paths,
+        &history::HistoryRecord::new("reset-official", None, None).in_scope(scope),
+    )?;
+    if format == OutputFormat::Json {
+        return report::write_json(
+            out,
+            &report::ok(
+                "reset-official",
+                json!({ "scope": scope.label(), "settings_path": scoped.settings_path }),
+            ),
+        );
+    }
+    writeln!(
+        out,
+        "Reset {} scope: removed preset overrides from {}.",
+        scope.label(),
+        scoped.settings_path.display()
+    )
+    .map_err(AppError::output)?;
+    Ok(())
+}
+
+fn undo_switch(
+    cfg: &mut SwitcherConfig,
+    paths: &paths::AppPaths,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let entry = journal::step_back(paths, |snapshot| restore_snapshot(cfg, snapshot, paths))?;
+    record_active(cfg, "undo", paths)?;
+    if format == OutputFormat::Json {
+        return report::write_json(
+            out,
+            &report::ok(
+                "undo",
+                json!({ "undone": entry.operation, "active_preset": cfg.active_preset }),
+            ),
+        );
+    }
+    writeln!(out, "Undid '{}'.", entry.operation).map_err(AppError::output)?;
+    write_active_line(cfg, out)
+}
+
+fn redo_switch(
+    cfg: &mut SwitcherConfig,
+    paths: &paths::AppPaths,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let entry = journal::step_forward(paths, |snapshot| restore_snapshot(cfg, snapshot, paths))?;
+    record_active(cfg, "redo", paths)?;
+    if format == OutputFormat::Json {
+        return report::write_json(
+            out,
+            &report::ok(
+                "redo",
+                json!({ "redone": entry.operation, "active_preset": cfg.active_preset }),
+            ),
+        );
+    }
+    writeln!(out, "Redid '{}'.", entry.operation).map_err(AppError::output)?;
+    write_active_line(cfg, out)
+}
+
+fn restore_snapshot(
+    cfg: &mut SwitcherConfig,
+    snapshot: &journal::Snapshot,
+    paths: &paths::AppPaths,
+) -> Result<()> {
+    let env = snapshot.resolved_env(paths)?;
+    settings::plan_restore_env(paths, env, snapshot.owned_env.clone())?.commit(paths)?;
+    cfg.active_preset = snapshot
+        .active_preset
+        .clone()
+        .filter(|name| cfg.presets.contains_key(name));
+    config::save(paths, cfg)
+}
+
+/// Logs an operation against whichever preset it left active.
+fn record_active(cfg: &SwitcherConfig, operation: &str, paths: &paths::AppPaths) -> Result<()> {
+    let name = cfg.active_preset.as_deref();
+    let preset = name.and_then(|name| cfg.presets.get(name));
+    history::append(paths, &history::HistoryRecord::new(operation, name, preset))
+}
+
+fn show_history(
+    paths: &paths::AppPaths,
+    since_ms: Option<u64>,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let records = history::read(paths, since_ms)?;
+    if format == OutputFormat::Json {
+        return report::write_json(out, &json!({ "ok": true, "history": records }));
+    }
+
+    if records.is_empty() {
+        writeln!(out, "No history.").map_err(AppError::output)?;
+        return Ok(());
+    }
+    for record in &records {
+        let mut line = format!(
+            "{}  {:<14}",
+            clock::format_utc(record.timestamp_ms),
+            record.operation
+        );
+        if let Some(preset) = &record.preset {
+            line.push_str(&format!(" {preset}"));
+        }
+        if let Some(scope) = &record.scope {
+            line.push_str(&format!(" [{scope}]"));
+        }
+        match (&record.provider, &record.base_url_host) {
+            (Some(provider), Some(host)) => line.push_str(&format!(" ({provider}, {host})")),
+            (Some(provider), None) => line.push_str(&format!(" ({provider})")),
+            _ => {}
+        }
+        writeln!(out, "{}", line.trim_end()).map_err(AppError::output)?;
+    }
+    Ok(())
+}
+
+fn run_doctor(paths: &paths::AppPaths, format: OutputFormat, out: &mut dyn Write) -> Result<()> {
+    let checks = doctor::run(paths);
+    let count = |status| checks.iter().filter(|check| check.status == status).count();
+    let (passed, warned, failed) = (
+        count(doctor::Status::Pass),
+        count(doctor::Status::Warn),
+        count(doctor::Status::Fail),
+    );
 
+    let result = json!({
+        "checks": checks,
+        "summary": { "pass": passed, "warn": warned, "fail": failed },
+    });
+    if format == OutputFormat::Json {
+        if failed > 0 {
+            // Written by `run` as part of the error envelope.
+            return Err(AppError::DoctorChecksFailed {
+                failures: failed,
+                report: result,
+            });
+        }
+        return report::write_json(out, &report::ok("doctor", result));
+    }
+    for check in &checks {
+        writeln!(
+            out,
+            "[{}] {}: {}",
+            check.status.label(),
+            check.name,
+            check.detail
+        )
+        .map_err(AppError::output)?;
+    }
     writeln!(
         out,
-        "Switched to preset '{preset_name}'. New requests will use this model configuration."
+        "{passed} passed, {warned} warning(s), {failed} failure(s)."
     )
     .map_err(AppError::output)?;
+    if failed > 0 {
+        return Err(AppError::DoctorChecksFailed {
+            failures: failed,
+            report: result,
+        });
+    }
     Ok(())
 }
 
-fn add_preset(
+fn write_active_line(cfg: &SwitcherConfig, out: &mut dyn Write) -> Result<()> {
+    match cfg.active_preset.as_deref() {
+        Some(name) => writeln!(out, "Active preset: {name}"),
+        None => writeln!(out, "No active preset."),
+    }
+    .map_err(AppError::output)
+}
+
+fn run_backups_command(
     cfg: &mut SwitcherConfig,
-    args: cli::AddArgs,
+    command: cli::BackupCommands,
     paths: &paths::AppPaths,
+    format: OutputFormat,
     out: &mut dyn Write,
 ) -> Result<()> {
-    let (name, preset) = args.into_name_and_preset();
-    if cfg.presets.contains_key(&name) {
-        return Err(AppError::PresetAlreadyExists(name));
+    match command {
+        cli::BackupCommands::List => list_backups(paths, format, out),
+        cli::BackupCommands::Restore { backup } => restore_backup(cfg, &backup, paths, format, out),
+        cli::BackupCommands::Prune { keep } => {
+            let removed = backups::prune(paths, keep)?;
+            if format == OutputFormat::Json {
+                return report::write_json(
+                    out,
+                    &report::ok("backups prune", json!({ "removed": removed.len() })),
+                );
+            }
+            writeln!(out, "Removed {} backup(s).", removed.len()).map_err(AppError::output)?;
+            Ok(())
+        }
+        cli::BackupCommands::Retention { keep: None } => {
+            let keep = cfg.backup_retention.unwrap_or(backups::DEFAULT_RETENTION);
+            if format == OutputFormat::Json {
+                return report::write_json(
+                    out,
+                    &report::ok("backups retention", json!({ "keep": keep })),
+                );
+            }
+            match keep {
+                0 => writeln!(out, "Backup retention: keep all backups."),
+                keep => writeln!(out, "Backup retention: keep newest {keep} backup(s)."),
+            }
+            .map_err(AppError::output)
+        }
+        cli::BackupCommands::Retention { keep: Some(keep) } => {
+            cfg.backup_retention = Some(keep);
+            config::save(paths, cfg)?;
+            let removed = backups::prune(paths, keep)?;
+            if format == OutputFormat::Json {
+                return report::write_json(
+                    out,
+                    &report::ok(
+                        "backups retention",
+                        json!({ "keep": keep, "removed": removed.len() }),
+                    ),
+                );
+            }
+            writeln!(out, "Backup retention set to {keep}.").map_err(AppError::output)?;
+            if !removed.is_empty() {
+                writeln!(out, "Removed {} backup(s).", removed.len()).map_err(AppError::output)?;
+            }
+            Ok(())
+        }
     }
-    preset.validate_ready(&name)?;
-
-    cfg.presets.insert(name.clone(), preset);
-    config::save(paths, cfg)?;
-
-    writeln!(out, "Saved preset '{name}'.").map_err(AppError::output)?;
-    writeln!(out, "Warning: this preset stores auth_token in plain text.")
-        .map_err(AppError::output)?;
-    Ok(())
 }
 
-fn remove_preset(
+fn run_vault_command(
     cfg: &mut SwitcherConfig,
-    preset_name: &str,
+    command: cli::VaultCommands,
     paths: &paths::AppPaths,
+    format: OutputFormat,
     out: &mut dyn Write,
 ) -> Result<()> {
-    if cfg.active_preset.as_deref() == Some(preset_name) {
-        return Err(AppError::CannotRemoveActivePreset(preset_name.to_owned()));
+    match command {
+        cli::VaultCommands::Init => {
+            vault::init(paths)?;
+            if format == OutputFormat::Json {
+                return report::write_json(
+                    out,
+                    &report::ok("vault init", json!({ "vault_path": paths.vault_path })),
+                );
+            }
+            writeln!(
+                out,
+                "Initialized token vault at {}.",
+                paths.vault_path.display()
+            )
+            .map_err(AppError::output)?;
+            writeln!(
+                out,
+                "Run `ccswitcher vault migrate` to move existing plain-text tokens into it."
+            )
+            .map_err(AppError::output)
+        }
+        cli::VaultCommands::Lock => {
+            let locked = vault::lock(paths)?;
+            if format == OutputFormat::Json {
+                return report::write_json(
+                    out,
+                    &report::ok("vault lock", json!({ "was_unlocked": locked })),
+                );
+            }
+            let message = if locked {
+                "Vault locked. The passphrase will be asked for on next use."
+            } else {
+                "Vault is already locked."
+            };
+            writeln!(out, "{message}").map_err(AppError::output)
+        }
+        cli::VaultCommands::Migrate => migrate_tokens_to_vault(cfg, paths, format, out),
     }
+}
 
-    if cfg.presets.remove(preset_name).is_none() {
-        return Err(AppError::PresetNotFound(preset_name.to_owned()));
+fn migrate_tokens_to_vault(
+    cfg: &mut SwitcherConfig,
+    paths: &paths::AppPaths,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let plain: Vec<String> = cfg
+        .presets
+        .iter()
+        .filter(|(_, preset)| {
+            matches!(TokenSource::parse(&preset.auth_token), TokenSource::Plain(token) if !token.is_empty())
+        })
+        .map(|(name, _)| name.clone())
+        .collect();
+    if plain.is_empty() && format == OutputFormat::Text {
+        writeln!(out, "No plain-text tokens to migrate.").map_err(AppError::output)?;
+        return Ok(());
     }
 
-    config::save(paths, cfg)?;
-    writeln!(out, "Removed preset '{preset_name}'.").map_err(AppError::output)?;
+    if !plain.is_empty() {
+        migrate_plain_tokens(cfg, &plain, paths)?;
+    }
+    if format == OutputFormat::Json {
+        return report::write_json(
+            out,
+            &report::ok("vault migrate", json!({ "migrated": plain })),
+        );
+    }
+    for name in &plain {
+        writeln!(out, "Moved token for preset '{name}' into the vault.")
+            .map_err(AppError::output)?;
+    }
     Ok(())
 }
 
-fn write_preset_details(out: &mut dyn Write, name: &str, preset: &Preset) -> Result<()> {
-    writeln!(out, "Active preset: {name}").map_err(AppError::output)?;
-    writeln!(out, "Provider: {}", preset.provider).map_err(AppError::output)?;
-    writeln!(out, "Base URL: {}", preset.base_url).map_err(AppError::output)?;
-    writeln!(out, "Haiku model: {}", preset.models.haiku_model).map_err(AppError::output)?;
-    writeln!(out, "Sonnet model: {}", preset.models.sonnet_model).map_err(AppError::output)?;
-    writeln!(out, "Opus model: {}", preset.models.opus_model).map_err(AppError::output)?;
+fn migrate_plain_tokens(
+    cfg: &mut SwitcherConfig,
+    plain: &[String],
+    paths: &paths::AppPaths,
+) -> Result<()> {
+    let mut unlocked = vault::unlock(paths)?;
+    for name in plain {
+        let preset = cfg
+            .presets
+            .get_mut(name)
+            .ok_or_else(|| AppError::PresetNotFound(name.clone()))?;
+        let entry = unlocked.seal(preset.auth_token.trim());
+        preset.auth_token = format!("vault:{entry}");
+    }
+    // Persist the vault first so config.json never references a missing entry.
+    unlocked.save(paths)?;
+    config::save(paths, cfg)
+}
+
+fn list_backups(paths: &paths::AppPaths, format: OutputFormat, out: &mut dyn Write) -> Result<()> {
+    let entries = backups::list(paths)?;
+    if format == OutputFormat::Json {
+        let backups: Vec<Value> = entries
+            .iter()
+            .map(|entry| {
+                json!({
+                    "id": entry.id,
+                    "created": clock::format_utc(entry.id),
+                    "size": entry.size,
+                    "preset": entry.preset,
+                })
+            })
+            .collect();
+        return report::write_json(out, &json!({ "ok": true, "backups": backups }));
+    }
+    if entries.is_empty() {
+        writeln!(out, "No backups.").map_err(AppError::output)?;
+        return Ok(());
+    }
+
+    writeln!(
+        out,
+        "{:<15} {:<21} {:>9}  Preset",
+        "ID", "Created (UTC)", "Size"
+    )
+    .map_err(AppError::output)?;
+    for entry in entries {
+        writeln!(
+            out,
+            "{:<15} {:<21} {:>7} B  {}",
+            entry.id,
+            clock::format_utc(entry.id),
+            entry.size,
+            entry.preset.as_deref().unwrap_or("-")
+        )
+        .map_err(AppError::output)?;
+    }
     Ok(())
 }
 
-fn reset_official(
+fn restore_backup(
     cfg: &mut SwitcherConfig,
+    selector: &str,
     paths: &paths::AppPaths,
+    format: OutputFormat,
     out: &mut dyn Write,
 ) -> Result<()> {
-    settings::reset_to_official(paths)?;
-    cfg.active_preset = None;
+    let entry = backups::find(paths, selector)?;
+    backups::restore(paths, &entry)?;
+
+    cfg.active_preset = entry
+        .preset
+        .clone()
+        .filter(|name| cfg.presets.contains_key(name));
     config::save(paths, cfg)?;
+
+    if format == OutputFormat::Json {
+        return report::write_json(
+            out,
+            &report::ok(
+                "backups restore",
+                json!({ "backup": entry.id, "active_preset": cfg.active_preset }),
+            ),
+        );
+    }
     writeln!(
         out,
-        "Reset complete. Official Claude model/provider defaults will be used for new requests."
+        "Restored settings.json from backup {} ({}).",
+        entry.id,
+        clock::format_utc(entry.id)
     )
     .map_err(AppError::output)?;
-    Ok(())
+    write_active_line(cfg, out)
 }
 
-fn install_slash_command(paths: &paths::AppPaths, out: &mut dyn Write) -> Result<()> {
-    let command_dir = paths.claude_home.join("commands");
-    fsutil::ensure_directory(&command_dir)?;
-    let command_path = command_dir.join("switchmodel.md");
-    let template = include_str!("../templates/switchmodel.md");
-    fsutil::write_text_atomic(&command_path, template)?;
+fn install_slash_command(
+    paths: &paths::AppPaths,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let command_path = paths.switchmodel_command_path();
+    if let Some(command_dir) = command_path.parent() {
+        fsutil::ensure_directory(command_dir)?;
+    }
+    fsutil::write_text_atomic_keeping_mode(&command_path, SWITCHMODEL_TEMPLATE)?;
     ensure_bash_permission_rule(paths)?;
+    if format == OutputFormat::Json {
+        return report::write_json(
+            out,
+            &report::ok(
+                "install",
+                json!({
+                    "command_path": command_path,
+                    "settings_local_path": paths.settings_local_path,
+                    "permission_rule": BASH_PERMISSION_RULE,
+                }),
+            ),
+        );
+    }
     writeln!(out, "Installed slash command: {}", command_path.display())
         .map_err(AppError::output)?;
     writeln!(
@@ -198,7 +2106,6 @@ fn install_slash_command(paths: &paths::AppPaths, out: &mut dyn Write) -> Result
 }
 
 fn ensure_bash_permission_rule(paths: &paths::AppPaths) -> Result<()> {
-    const RULE: &str = "Bash(ccswitcher:*)";
     let path = &paths.settings_local_path;
 
     let mut root = if path.exists() {
@@ -223,8 +2130,11 @@ fn ensure_bash_permission_rule(paths: &paths::AppPaths) -> Result<()> {
         .as_array_mut()
         .ok_or_else(|| AppError::invalid_json_root(path))?;
 
-    if !allow.iter().any(|v| v.as_str() == Some(RULE)) {
-        allow.push(Value::String(RULE.to_owned()));
+    if !allow
+        .iter()
+        .any(|v| v.as_str() == Some(BASH_PERMISSION_RULE))
+    {
+        allow.push(Value::String(BASH_PERMISSION_RULE.to_owned()));
     }
     permissions
         .entry("deny".to_owned())
@@ -233,7 +2143,7 @@ fn ensure_bash_permission_rule(paths: &paths::AppPaths) -> Result<()> {
         .entry("ask".to_owned())
         .or_insert_with(|| json!([]));
 
-    fsutil::write_json_atomic(path, &root)
+    fsutil::write_json_atomic_keeping_mode(path, &root)
 }
 
 fn run_interactive_menu(
@@ -253,12 +2163,16 @@ fn run_interactive_menu(
         writeln!(out, "2. current preset").map_err(AppError::output)?;
         writeln!(out, "3. use preset").map_err(AppError::output)?;
         writeln!(out, "4. add preset").map_err(AppError::output)?;
-        writeln!(out, "5. remove preset").map_err(AppError::output)?;
-        writeln!(out, "6. reset official").map_err(AppError::output)?;
-        writeln!(out, "7. install /switchmodel command").map_err(AppError::output)?;
+        writeln!(out, "5. edit preset").map_err(AppError::output)?;
+        writeln!(out, "6. rename preset").map_err(AppError::output)?;
+        writeln!(out, "7. clone preset").map_err(AppError::output)?;
+        writeln!(out, "8. remove preset").map_err(AppError::output)?;
+        writeln!(out, "9. reset official").map_err(AppError::output)?;
+        writeln!(out, "10. install /switchmodel command").map_err(AppError::output)?;
+        writeln!(out, "11. add preset from current settings").map_err(AppError::output)?;
         writeln!(out, "0. exit").map_err(AppError::output)?;
 
-        let action = prompt_line(out, "Select [0-7]").map(|v| v.to_ascii_lowercase())?;
+        let action = prompt_line(out, "Select [0-11]").map(|v| v.to_ascii_lowercase())?;
         if matches!(action.as_str(), "0" | "exit" | "quit") {
             writeln!(out).map_err(AppError::output)?;
             writeln!(out, "Bye.").map_err(AppError::output)?;
@@ -268,8 +2182,8 @@ fn run_interactive_menu(
         writeln!(out).map_err(AppError::output)?;
         writeln!(out, "{RESULT_START}").map_err(AppError::output)?;
         match action.as_str() {
-            "1" | "list" => list_presets(cfg, out)?,
-            "2" | "current" => show_current(cfg, out)?,
+            "1" | "list" => list_presets(cfg, OutputFormat::Text, out)?,
+            "2" | "current" => show_current(cfg, paths, OutputFormat::Text, out)?,
             "3" | "use" => {
                 if let Some(preset) = prompt_select_preset(
                     cfg,
@@ -277,39 +2191,100 @@ fn run_interactive_menu(
                     "Select a preset to use",
                     "No presets configured. Use action 4 to add one first.",
                 )? {
-                    use_preset(cfg, &preset, paths, out)?;
+                    use_preset(cfg, &preset, paths, OutputFormat::Text, out)?;
                 }
             }
             "4" | "add" => {
-                let maybe_args = prompt_add_args(out)?;
+                let catalog = provider::Catalog::load(paths)?;
+                let maybe_args = prompt_add_args(&catalog, out)?;
                 if let Some(args) = maybe_args {
-                    add_preset(cfg, args, paths, out)?;
+                    add_preset(cfg, args, paths, OutputFormat::Text, out)?;
                 } else {
                     writeln!(out, "Add preset cancelled.").map_err(AppError::output)?;
                 }
             }
-            "5" | "remove" => {
+            "5" | "edit" => {
+                if let Some(preset) = prompt_select_preset(
+                    cfg,
+                    out,
+                    "Select a preset to edit",
+                    "No presets configured. Use action 4 to add one first.",
+                )? {
+                    let catalog = provider::Catalog::load(paths)?;
+                    if let Some(overrides) =
+                        prompt_edit_overrides(cfg, &catalog, &preset, &preset, out)?
+                    {
+                        let args = cli::EditArgs { preset, overrides };
+                        edit_preset(cfg, args, paths, OutputFormat::Text, out)?;
+                    } else {
+                        writeln!(out, "Edit preset cancelled.").map_err(AppError::output)?;
+                    }
+                }
+            }
+            "6" | "rename" => {
+                if let Some(preset) = prompt_select_preset(
+                    cfg,
+                    out,
+                    "Select a preset to rename",
+                    "No presets configured. Nothing to rename.",
+                )? {
+                    let new_name = prompt_required(out, "New preset name")?;
+                    rename_preset(cfg, &preset, &new_name, paths, OutputFormat::Text, out)?;
+                }
+            }
+            "7" | "clone" => {
+                if let Some(source) = prompt_select_preset(
+                    cfg,
+                    out,
+                    "Select a preset to clone",
+                    "No presets configured. Use action 4 to add one first.",
+                )? {
+                    let target = prompt_required(out, "New preset name")?;
+                    let overrides =
+                        if prompt_yes_no(out, "Override fields on the clone? [y/N]", false)? {
+                            let catalog = provider::Catalog::load(paths)?;
+                            prompt_edit_overrides(cfg, &catalog, &source, &target, out)?
+                        } else {
+                            Some(cli::PresetOverrides::default())
+                        };
+                    if let Some(overrides) = overrides {
+                        let args = cli::CloneArgs {
+                            source,
+                            target,
+                            overrides,
+                        };
+                        clone_preset(cfg, args, paths, OutputFormat::Text, out)?;
+                    } else {
+                        writeln!(out, "Clone preset cancelled.").map_err(AppError::output)?;
+                    }
+                }
+            }
+            "8" | "remove" => {
                 if let Some(preset) = prompt_select_preset(
                     cfg,
                     out,
                     "Select a preset to remove",
                     "No presets configured. Nothing to remove.",
                 )? {
-                    remove_preset(cfg, &preset, paths, out)?;
+                    remove_preset(cfg, &preset, paths, OutputFormat::Text, out)?;
                 }
             }
-            "6" | "reset" | "reset-official" => {
+            "9" | "reset" | "reset-official" => {
                 let confirm = prompt_line(
                     out,
                     "Type RESET to confirm official reset (or Enter to cancel)",
                 )?;
                 if confirm == "RESET" {
-                    reset_official(cfg, paths, out)?;
+                    reset_official(cfg, paths, OutputFormat::Text, out)?;
                 } else {
                     writeln!(out, "Reset cancelled.").map_err(AppError::output)?;
                 }
             }
-            "7" | "install" => install_slash_command(paths, out)?,
+            "10" | "install" => install_slash_command(paths, OutputFormat::Text, out)?,
+            "11" | "capture" => {
+                let name = prompt_required(out, "Preset name")?;
+                add_preset_from_current(cfg, &name, paths, OutputFormat::Text, out)?;
+            }
             _ => writeln!(out, "Invalid selection.").map_err(AppError::output)?,
         }
         writeln!(out, "{RESULT_END}").map_err(AppError::output)?;
@@ -318,35 +2293,45 @@ fn run_interactive_menu(
     Ok(())
 }
 
-fn prompt_add_args(out: &mut dyn Write) -> Result<Option<cli::AddArgs>> {
+fn prompt_add_args(
+    catalog: &provider::Catalog,
+    out: &mut dyn Write,
+) -> Result<Option<cli::AddArgs>> {
     writeln!(out, "Add preset wizard (terminal interactive mode).").map_err(AppError::output)?;
     writeln!(out, "For optional fields, press Enter to use default.").map_err(AppError::output)?;
 
     let name = prompt_required(out, "Preset name")?;
-    let provider_input = prompt_line(out, "Provider tag (optional, default: custom)")?;
-    let provider = if provider_input.eq_ignore_ascii_case("glm") {
-        ProviderKind::Glm
-    } else {
-        ProviderKind::Custom
-    };
+    let provider = prompt_provider(catalog, out, ProviderKind::Custom)?;
+    let template = catalog.template(&provider)?;
 
-    let base_url = prompt_required(out, "Base URL")?;
+    let base_url = prompt_template_field(out, "Base URL", template.map(|t| &t.base_url))?;
 
-    let auth_token = prompt_required(out, "Auth token")?;
-    let default_model = prompt_required(
-        out,
-        "Default model (applies to haiku/sonnet/opus unless overridden)",
-    )?;
-    let separate_models =
-        prompt_yes_no(out, "Set separate haiku/sonnet/opus models? [y/N]", false)?;
-    let (haiku, sonnet, opus) = if separate_models {
+    if let Some(hint) = template.and_then(|t| t.token_hint.as_deref()) {
+        writeln!(out, "Token: {hint}").map_err(AppError::output)?;
+    }
+    let auth_token = prompt_required(out, "Auth token (or env:VAR, file:PATH, cmd:COMMAND)")?;
+    let (haiku, sonnet, opus) = if let Some(template) = template {
         (
-            prompt_with_default(out, "Haiku model", &default_model)?,
-            prompt_with_default(out, "Sonnet model", &default_model)?,
-            prompt_with_default(out, "Opus model", &default_model)?,
+            prompt_template_field(out, "Haiku model", Some(&template.haiku_model))?,
+            prompt_template_field(out, "Sonnet model", Some(&template.sonnet_model))?,
+            prompt_template_field(out, "Opus model", Some(&template.opus_model))?,
         )
     } else {
-        (default_model.clone(), default_model.clone(), default_model)
+        let default_model = prompt_required(
+            out,
+            "Default model (applies to haiku/sonnet/opus unless overridden)",
+        )?;
+        let separate_models =
+            prompt_yes_no(out, "Set separate haiku/sonnet/opus models? [y/N]", false)?;
+        if separate_models {
+            (
+                prompt_with_default(out, "Haiku model", &default_model)?,
+                prompt_with_default(out, "Sonnet model", &default_model)?,
+                prompt_with_default(out, "Opus model", &default_model)?,
+            )
+        } else {
+            (default_model.clone(), default_model.clone(), default_model)
+        }
     };
 
     let http_proxy = prompt_optional(out, "HTTP proxy", "not set")?;
@@ -354,26 +2339,133 @@ fn prompt_add_args(out: &mut dyn Write) -> Result<Option<cli::AddArgs>> {
     let mcp_tool_timeout = prompt_optional(out, "MCP tool timeout (ms)", "not set")?;
     let disable_nonessential_traffic =
         prompt_optional_bool(out, "Disable nonessential traffic? (true/false)", "not set")?;
+    let mut env = Vec::new();
+    for key in template
+        .iter()
+        .flat_map(|t| &t.required)
+        .filter(|field| !matches!(field.as_str(), "base_url" | "haiku" | "sonnet" | "opus"))
+    {
+        env.push((key.clone(), prompt_required(out, &format!("Env {key}"))?));
+    }
+    env.extend(prompt_extra_env(out, None)?);
 
     let args = cli::AddArgs {
         name,
         provider,
-        base_url,
-        auth_token,
-        haiku,
-        sonnet,
-        opus,
+        base_url: Some(base_url),
+        auth_token: Some(auth_token),
+        auth_token_env: None,
+        auth_token_file: None,
+        auth_token_cmd: None,
+        haiku: Some(haiku),
+        sonnet: Some(sonnet),
+        opus: Some(opus),
         http_proxy,
         api_timeout_ms,
         mcp_tool_timeout,
         disable_nonessential_traffic,
+        env,
     };
 
-    print_add_summary(out, &args)?;
+    print_add_summary(out, &args, template)?;
     let confirm = prompt_yes_no(out, "Save this preset? [Y/n]", true)?;
     if confirm { Ok(Some(args)) } else { Ok(None) }
 }
 
+/// Prompts with the template's value as default, or requires a value when it has none.
+fn prompt_template_field(
+    out: &mut dyn Write,
+    prompt: &str,
+    default_value: Option<&Option<String>>,
+) -> Result<String> {
+    match default_value.and_then(Option::as_deref) {
+        Some(default_value) => prompt_with_default(out, prompt, default_value),
+        None => prompt_required(out, prompt),
+    }
+}
+
+fn prompt_edit_overrides(
+    cfg: &SwitcherConfig,
+    catalog: &provider::Catalog,
+    preset_name: &str,
+    target_name: &str,
+    out: &mut dyn Write,
+) -> Result<Option<cli::PresetOverrides>> {
+    let current = cfg
+        .presets
+        .get(preset_name)
+        .ok_or_else(|| AppError::PresetNotFound(preset_name.to_owned()))?;
+
+    writeln!(out, "Edit preset wizard (terminal interactive mode).").map_err(AppError::output)?;
+    writeln!(
+        out,
+        "Press Enter to keep the current value. For optional fields, type - to clear."
+    )
+    .map_err(AppError::output)?;
+
+    let provider = prompt_provider(catalog, out, current.provider.clone())?;
+    let base_url = prompt_with_default(out, "Base URL", &current.base_url)?;
+    let auth_token = prompt_line(out, "Auth token (default: ****, Enter to keep)")?;
+    let haiku = prompt_with_default(out, "Haiku model", &current.models.haiku_model)?;
+    let sonnet = prompt_with_default(out, "Sonnet model", &current.models.sonnet_model)?;
+    let opus = prompt_with_default(out, "Opus model", &current.models.opus_model)?;
+
+    let current_proxy = current
+        .network
+        .as_ref()
+        .and_then(|network| network.http_proxy.as_deref());
+    let current_api_timeout = current
+        .timeouts
+        .as_ref()
+        .and_then(|timeouts| timeouts.api_timeout_ms.as_deref());
+    let current_mcp_timeout = current
+        .timeouts
+        .as_ref()
+        .and_then(|timeouts| timeouts.mcp_tool_timeout.as_deref());
+    let current_traffic = current
+        .flags
+        .as_ref()
+        .and_then(|flags| flags.disable_nonessential_traffic.as_deref());
+
+    let http_proxy = prompt_optional_edit(out, "HTTP proxy", current_proxy)?;
+    let api_timeout_ms = prompt_optional_edit(out, "API timeout (ms)", current_api_timeout)?;
+    let mcp_tool_timeout = prompt_optional_edit(out, "MCP tool timeout (ms)", current_mcp_timeout)?;
+    let traffic = prompt_optional_bool_edit(
+        out,
+        "Disable nonessential traffic? (true/false)",
+        current_traffic,
+    )?;
+    let mut unset_env = Vec::new();
+    let env = prompt_extra_env(out, Some(&mut unset_env))?;
+
+    let overrides = cli::PresetOverrides {
+        provider: Some(provider).filter(|p| *p != current.provider),
+        base_url: Some(base_url).filter(|v| *v != current.base_url),
+        auth_token: Some(auth_token).filter(|v| !v.is_empty()),
+        haiku: Some(haiku).filter(|v| *v != current.models.haiku_model),
+        sonnet: Some(sonnet).filter(|v| *v != current.models.sonnet_model),
+        opus: Some(opus).filter(|v| *v != current.models.opus_model),
+        http_proxy,
+        api_timeout_ms,
+        mcp_tool_timeout,
+        disable_nonessential_traffic: traffic.flatten(),
+        unset_disable_nonessential_traffic: traffic == Some(None),
+        env,
+        unset_env,
+    };
+
+    print_edit_summary(out, target_name, current, &overrides)?;
+    if !overrides.has_changes() {
+        return Ok(Some(overrides));
+    }
+    let confirm = prompt_yes_no(out, "Save these changes? [Y/n]", true)?;
+    if confirm {
+        Ok(Some(overrides))
+    } else {
+        Ok(None)
+    }
+}
+
 fn prompt_line(out: &mut dyn Write, prompt: &str) -> Result<String> {
     write!(out, "{prompt}: ").map_err(AppError::output)?;
     out.flush().map_err(AppError::output)?;
@@ -384,6 +2476,46 @@ fn prompt_line(out: &mut dyn Write, prompt: &str) -> Result<String> {
     Ok(line.trim().to_owned())
 }
 
+/// Offers every catalog provider by number or name; Enter keeps `default_value`.
+fn prompt_provider(
+    catalog: &provider::Catalog,
+    out: &mut dyn Write,
+    default_value: ProviderKind,
+) -> Result<ProviderKind> {
+    let mut choices: Vec<(ProviderKind, &str)> = catalog
+        .entries()
+        .iter()
+        .map(|(kind, template)| (kind.clone(), template.label.as_str()))
+        .collect();
+    choices.push((ProviderKind::Custom, "enter URL and models yourself"));
+
+    writeln!(out, "Providers:").map_err(AppError::output)?;
+    for (idx, (kind, label)) in choices.iter().enumerate() {
+        writeln!(out, "{}. {kind} ({label})", idx + 1).map_err(AppError::output)?;
+    }
+    let labeled = format!(
+        "Provider number or name [1-{}] (default: {default_value})",
+        choices.len()
+    );
+    loop {
+        let value = prompt_line(out, &labeled)?;
+        if value.is_empty() {
+            return Ok(default_value);
+        }
+        let chosen = match value.parse::<usize>() {
+            Ok(index) if (1..=choices.len()).contains(&index) => Some(choices[index - 1].0.clone()),
+            _ => value
+                .parse::<ProviderKind>()
+                .ok()
+                .filter(|kind| choices.iter().any(|(choice, _)| choice == kind)),
+        };
+        match chosen {
+            Some(provider) => return Ok(provider),
+            None => writeln!(out, "Unknown provider '{value}'.").map_err(AppError::output)?,
+        }
+    }
+}
+
 fn prompt_required(out: &mut dyn Write, prompt: &str) -> Result<String> {
     let labeled = format!("{prompt} (required)");
     loop {
@@ -459,6 +2591,54 @@ fn prompt_optional(
     }
 }
 
+fn prompt_optional_edit(
+    out: &mut dyn Write,
+    prompt: &str,
+    current: Option<&str>,
+) -> Result<Option<String>> {
+    let value = prompt_line(
+        out,
+        &format!(
+            "{prompt} (current: {}, Enter to keep, - to clear)",
+            current.unwrap_or("not set")
+        ),
+    )?;
+    match value.as_str() {
+        "" => Ok(None),
+        "-" => Ok(current.map(|_| String::new())),
+        _ => Ok(Some(value)),
+    }
+}
+
+/// Collects repeated `KEY=VALUE` entries; when `unset_env` is given, `-KEY` marks a key for removal.
+fn prompt_extra_env(
+    out: &mut dyn Write,
+    mut unset_env: Option<&mut Vec<String>>,
+) -> Result<Vec<(String, String)>> {
+    let label = if unset_env.is_some() {
+        "Extra env var KEY=VALUE, or -KEY to remove (optional, Enter to finish)"
+    } else {
+        "Extra env var KEY=VALUE (optional, Enter to finish)"
+    };
+    let mut env = Vec::new();
+    loop {
+        let value = prompt_line(out, label)?;
+        if value.is_empty() {
+            return Ok(env);
+        }
+        if let Some(unset_env) = unset_env.as_deref_mut()
+            && let Some(key) = value.strip_prefix('-')
+        {
+            unset_env.push(key.trim().to_owned());
+            continue;
+        }
+        match cli::parse_env_pair(&value) {
+            Ok(pair) => env.push(pair),
+            Err(message) => writeln!(out, "{message}").map_err(AppError::output)?,
+        }
+    }
+}
+
 fn prompt_optional_bool(
     out: &mut dyn Write,
     prompt: &str,
@@ -478,20 +2658,68 @@ fn prompt_optional_bool(
     }
 }
 
-fn print_add_summary(out: &mut dyn Write, args: &cli::AddArgs) -> Result<()> {
+/// `None` keeps the current value, `Some(None)` clears it (typed as `-`).
+fn prompt_optional_bool_edit(
+    out: &mut dyn Write,
+    prompt: &str,
+    current: Option<&str>,
+) -> Result<Option<Option<bool>>> {
+    let labeled = format!(
+        "{prompt} (current: {}, Enter to keep, - to clear)",
+        current.unwrap_or("not set")
+    );
+    loop {
+        let value = prompt_line(out, &labeled)?;
+        match value.to_ascii_lowercase().as_str() {
+            "" => return Ok(None),
+            "-" | "none" => return Ok(current.map(|_| None)),
+            "true" | "t" | "1" | "yes" | "y" => return Ok(Some(Some(true))),
+            "false" | "f" | "0" | "no" | "n" => return Ok(Some(Some(false))),
+            _ => writeln!(out, "Please enter true, false or -.").map_err(AppError::output)?,
+        }
+    }
+}
+
+fn print_add_summary(
+    out: &mut dyn Write,
+    args: &cli::AddArgs,
+    template: Option<&provider::ProviderTemplate>,
+) -> Result<()> {
     writeln!(out).map_err(AppError::output)?;
     writeln!(out, "Preset summary:").map_err(AppError::output)?;
     writeln!(out, "- name: {}", args.name).map_err(AppError::output)?;
     writeln!(out, "- provider: {}", args.provider).map_err(AppError::output)?;
-    writeln!(out, "- base_url: {}", args.base_url).map_err(AppError::output)?;
-    writeln!(out, "- auth_token: ****").map_err(AppError::output)?;
-    writeln!(out, "- haiku_model: {}", args.haiku).map_err(AppError::output)?;
-    writeln!(out, "- sonnet_model: {}", args.sonnet).map_err(AppError::output)?;
-    writeln!(out, "- opus_model: {}", args.opus).map_err(AppError::output)?;
+    let provider_default = "provider default";
+    writeln!(
+        out,
+        "- base_url: {}",
+        args.base_url.as_deref().unwrap_or(provider_default)
+    )
+    .map_err(AppError::output)?;
+    writeln!(
+        out,
+        "- auth_token: {}",
+        TokenSource::parse(&args.auth_token_value()).describe()
+    )
+    .map_err(AppError::output)?;
+    for (label, value) in [
+        ("haiku_model", &args.haiku),
+        ("sonnet_model", &args.sonnet),
+        ("opus_model", &args.opus),
+    ] {
+        writeln!(
+            out,
+            "- {label}: {}",
+            value.as_deref().unwrap_or(provider_default)
+        )
+        .map_err(AppError::output)?;
+    }
     writeln!(
         out,
         "- http_proxy: {}",
-        args.http_proxy.as_deref().unwrap_or("not set")
+        args.http_proxy
+            .as_deref()
+            .map_or_else(|| "not set".to_owned(), settings::mask_url_userinfo)
     )
     .map_err(AppError::output)?;
     writeln!(
@@ -512,6 +2740,87 @@ fn print_add_summary(out: &mut dyn Write, args: &cli::AddArgs) -> Result<()> {
         None => "not set",
     };
     writeln!(out, "- disable_nonessential_traffic: {traffic}").map_err(AppError::output)?;
+    for (key, value) in template.iter().flat_map(|t| &t.extra_env) {
+        if !args.env.iter().any(|(set, _)| set == key) {
+            let value = settings::display_env_value(key, &Value::String(value.clone()));
+            writeln!(out, "- env {key}: {value} ({provider_default})").map_err(AppError::output)?;
+        }
+    }
+    for (key, value) in &args.env {
+        let value = settings::display_env_value(key, &Value::String(value.clone()));
+        writeln!(out, "- env {key}: {value}").map_err(AppError::output)?;
+    }
+    Ok(())
+}
+
+fn print_edit_summary(
+    out: &mut dyn Write,
+    preset_name: &str,
+    current: &Preset,
+    args: &cli::PresetOverrides,
+) -> Result<()> {
+    writeln!(out).map_err(AppError::output)?;
+    if !args.has_changes() {
+        writeln!(out, "No changes.").map_err(AppError::output)?;
+        return Ok(());
+    }
+
+    writeln!(out, "Changes for preset '{preset_name}':").map_err(AppError::output)?;
+    if let Some(provider) = &args.provider {
+        writeln!(out, "- provider: {} -> {provider}", current.provider)
+            .map_err(AppError::output)?;
+    }
+    if let Some(base_url) = &args.base_url {
+        writeln!(out, "- base_url: {} -> {base_url}", current.base_url)
+            .map_err(AppError::output)?;
+    }
+    if args.auth_token.is_some() {
+        writeln!(out, "- auth_token: **** -> ****").map_err(AppError::output)?;
+    }
+    if let Some(haiku) = &args.haiku {
+        writeln!(
+            out,
+            "- haiku_model: {} -> {haiku}",
+            current.models.haiku_model
+        )
+        .map_err(AppError::output)?;
+    }
+    if let Some(sonnet) = &args.sonnet {
+        writeln!(
+            out,
+            "- sonnet_model: {} -> {sonnet}",
+            current.models.sonnet_model
+        )
+        .map_err(AppError::output)?;
+    }
+    if let Some(opus) = &args.opus {
+        writeln!(out, "- opus_model: {} -> {opus}", current.models.opus_model)
+            .map_err(AppError::output)?;
+    }
+    for (label, value) in [
+        ("http_proxy", &args.http_proxy),
+        ("api_timeout_ms", &args.api_timeout_ms),
+        ("mcp_tool_timeout", &args.mcp_tool_timeout),
+    ] {
+        match value.as_deref() {
+            Some("") => writeln!(out, "- {label}: cleared").map_err(AppError::output)?,
+            Some(v) => writeln!(out, "- {label}: {}", settings::mask_url_userinfo(v))
+                .map_err(AppError::output)?,
+            None => {}
+        }
+    }
+    if let Some(traffic) = args.disable_nonessential_traffic {
+        writeln!(out, "- disable_nonessential_traffic: {traffic}").map_err(AppError::output)?;
+    } else if args.unset_disable_nonessential_traffic {
+        writeln!(out, "- disable_nonessential_traffic: cleared").map_err(AppError::output)?;
+    }
+    for key in &args.unset_env {
+        writeln!(out, "- env {key}: removed").map_err(AppError::output)?;
+    }
+    for (key, value) in &args.env {
+        let value = settings::display_env_value(key, &Value::String(value.clone()));
+        writeln!(out, "- env {key}: {value}").map_err(AppError::output)?;
+    }
     Ok(())
 }
 
@@ -1,6 +1,3 @@
 fn main() {
-    if let Err(err) = ccswitcher::run() {
-        eprintln!("Error: {err}");
-        std::process::exit(1);
-    }
+    std::process::exit(ccswitcher::run());
 }
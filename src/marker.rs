@@ -0,0 +1,313 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use blake2::{Blake2s256, Digest};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cli::{self, PresetOverrides},
+    config::{Preset, SwitcherConfig},
+    errors::{AppError, Result},
+    fsutil,
+    paths::{AppPaths, Scope},
+    state,
+};
+
+/// File that pins a directory tree to a preset, found by walking up from the cwd.
+pub const MARKER_FILE: &str = ".ccswitcher";
+
+/// Contents of a `.ccswitcher` file: either just a preset name, or a JSON
+/// object naming the preset plus fields to override for this directory.
+///
+/// Marker files usually arrive with a cloned repository, and an override such
+/// as `base_url` decides where the preset's token is sent. `auto` therefore
+/// only applies a marker whose exact contents were approved with
+/// `ccswitcher allow`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MarkerSpec {
+    pub preset: String,
+    /// Settings file `auto` writes to; defaults to the user scope.
+    #[serde(default)]
+    pub scope: Option<Scope>,
+    #[serde(default)]
+    pub base_url: Option<String>,
+    #[serde(default)]
+    pub haiku: Option<String>,
+    #[serde(default)]
+    pub sonnet: Option<String>,
+    #[serde(default)]
+    pub opus: Option<String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+impl MarkerSpec {
+    pub fn has_overrides(&self) -> bool {
+        self.base_url.is_some()
+            || self.haiku.is_some()
+            || self.sonnet.is_some()
+            || self.opus.is_some()
+            || !self.env.is_empty()
+    }
+
+    /// `preset` with this marker's overrides merged in.
+    pub fn apply_to(&self, preset: &Preset) -> Preset {
+        let mut preset = preset.clone();
+        PresetOverrides {
+            base_url: self.base_url.clone(),
+            haiku: self.haiku.clone(),
+            sonnet: self.sonnet.clone(),
+            opus: self.opus.clone(),
+            env: self.env.clone().into_iter().collect(),
+            ..PresetOverrides::default()
+        }
+        .apply_to(&mut preset);
+        preset
+    }
+
+    /// Fields that redirect the token or change the environment, or a scope
+    /// that writes the token into a committed file, for review before `allow`.
+    pub fn sensitive_fields(&self) -> Vec<&'static str> {
+        let mut fields = Vec::new();
+        if self.base_url.is_some() {
+            fields.push("base_url");
+        }
+        if !self.env.is_empty() {
+            fields.push("env");
+        }
+        if self.scope == Some(Scope::Project) {
+            fields.push("scope: project");
+        }
+        fields
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Marker {
+    pub path: PathBuf,
+    pub spec: MarkerSpec,
+    /// Hash of the file contents, which an approval is tied to.
+    pub digest: String,
+}
+
+/// `allowed-markers.json`: approved marker paths mapped to the content hash
+/// that was approved, so editing a marker requires approving it again.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct AllowedMarkers {
+    #[serde(default)]
+    markers: BTreeMap<PathBuf, String>,
+}
+
+impl Marker {
+    /// The named preset with the marker's overrides applied.
+    pub fn resolve(&self, cfg: &SwitcherConfig) -> Result<Preset> {
+        let preset = cfg
+            .presets
+            .get(&self.spec.preset)
+            .ok_or_else(|| AppError::PresetNotFound(self.spec.preset.clone()))?;
+        Ok(self.spec.apply_to(preset))
+    }
+}
+
+/// The nearest marker file in `start` or one of its parents.
+pub fn discover(start: &Path) -> Result<Option<Marker>> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(MARKER_FILE))
+        .find(|path| path.is_file())
+        .map(|path| read(&path))
+        .transpose()
+}
+
+pub fn read(path: &Path) -> Result<Marker> {
+    let raw = fs::read_to_string(path).map_err(|err| AppError::io(path, err))?;
+    let invalid = |reason: String| AppError::InvalidMarker {
+        path: path.to_path_buf(),
+        reason,
+    };
+
+    let spec = if raw.trim_start().starts_with('{') {
+        serde_json::from_str::<MarkerSpec>(&raw).map_err(|err| invalid(err.to_string()))?
+    } else {
+        let name = raw
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'))
+            .unwrap_or_default();
+        MarkerSpec {
+            preset: name.to_owned(),
+            ..MarkerSpec::default()
+        }
+    };
+
+    if spec.preset.trim().is_empty() {
+        return Err(invalid("no preset name given".to_owned()));
+    }
+    for (key, value) in &spec.env {
+        cli::parse_env_pair(&format!("{key}={value}")).map_err(invalid)?;
+    }
+    Ok(Marker {
+        path: path.to_path_buf(),
+        spec: MarkerSpec {
+            preset: spec.preset.trim().to_owned(),
+            ..spec
+        },
+        digest: digest(raw.as_bytes()),
+    })
+}
+
+fn digest(contents: &[u8]) -> String {
+    Blake2s256::digest(contents)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Approvals are keyed by the resolved path, so symlinked checkouts share them.
+fn allow_key(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn load_allowed(paths: &AppPaths) -> Result<AllowedMarkers> {
+    let path = &paths.allowed_markers_path;
+    if !path.exists() {
+        return Ok(AllowedMarkers::default());
+    }
+    let raw = fs::read_to_string(path).map_err(|err| AppError::io(path, err))?;
+    serde_json::from_str(&raw).map_err(|err| AppError::json(path, err))
+}
+
+/// Whether `marker` was approved with its current contents.
+pub fn is_allowed(paths: &AppPaths, marker: &Marker) -> Result<bool> {
+    let allowed = load_allowed(paths)?;
+    Ok(allowed.markers.get(&allow_key(&marker.path)) == Some(&marker.digest))
+}
+
+/// Approves `marker` as it is now for `auto`.
+pub fn allow(paths: &AppPaths, marker: &Marker) -> Result<()> {
+    let mut allowed = load_allowed(paths)?;
+    allowed
+        .markers
+        .insert(allow_key(&marker.path), marker.digest.clone());
+    fsutil::write_json_atomic(&paths.allowed_markers_path, &allowed)
+}
+
+/// Withdraws the approval of the marker at `path`; `false` when it had none.
+pub fn deny(paths: &AppPaths, path: &Path) -> Result<bool> {
+    let mut allowed = load_allowed(paths)?;
+    if allowed.markers.remove(&allow_key(path)).is_none() {
+        return Ok(false);
+    }
+    fsutil::write_json_atomic(&paths.allowed_markers_path, &allowed)?;
+    Ok(true)
+}
+
+/// Approved markers whose current contents still select the preset `name`.
+pub fn approved_for(paths: &AppPaths, name: &str) -> Result<Vec<PathBuf>> {
+    let allowed = load_allowed(paths)?;
+    Ok(allowed
+        .markers
+        .iter()
+        .filter_map(|(path, approved)| read(path).ok().filter(|marker| &marker.digest == approved))
+        .filter(|marker| marker.spec.preset == name)
+        .map(|marker| marker.path)
+        .collect())
+}
+
+/// `preset` as `auto` last applied it to `paths`' settings file: with the
+/// overrides of the recorded marker, if that marker still selects `name`.
+pub fn as_applied(paths: &AppPaths, name: &str, preset: &Preset) -> Preset {
+    let recorded = state::load(paths).ok().and_then(|state| state.marker);
+    match recorded.and_then(|path| read(&path).ok()) {
+        Some(marker)
+            if marker.spec.preset == name && is_allowed(paths, &marker).unwrap_or(false) =>
+        {
+            marker.spec.apply_to(preset)
+        }
+        _ => preset.clone(),
+    }
+}
+
+/// Remembers which marker chose the preset in `paths`' settings file.
+pub fn record(paths: &AppPaths, marker: Option<PathBuf>) -> Result<()> {
+    let mut switcher_state = state::load(paths)?;
+    switcher_state.marker = marker;
+    state::save(paths, &switcher_state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_plain_names_and_json_overrides() {
+        let dir = tempfile::TempDir::new().expect("tempdir");
+        let path = dir.path().join(MARKER_FILE);
+
+        fs::write(&path, "# client A\n\n  glm-work  \n").expect("write marker");
+        let marker = read(&path).expect("plain marker");
+        assert_eq!(marker.spec.preset, "glm-work");
+        assert!(!marker.spec.has_overrides());
+
+        fs::write(
+            &path,
+            r#"{"preset": "glm-work", "scope": "local", "sonnet": "GLM-5", "env": {"NO_PROXY": "*"}}"#,
+        )
+        .expect("write marker");
+        let marker = read(&path).expect("json marker");
+        assert_eq!(marker.spec.scope, Some(Scope::Local));
+        assert_eq!(marker.spec.sonnet.as_deref(), Some("GLM-5"));
+        assert!(marker.spec.has_overrides());
+
+        fs::write(&path, r#"{"preset": "x", "auth_token": "leak"}"#).expect("write marker");
+        assert!(matches!(read(&path), Err(AppError::InvalidMarker { .. })));
+        fs::write(
+            &path,
+            r#"{"preset": "x", "env": {"ANTHROPIC_BASE_URL": "y"}}"#,
+        )
+        .expect("write marker");
+        assert!(matches!(read(&path), Err(AppError::InvalidMarker { .. })));
+    }
+
+    #[test]
+    fn approvals_are_tied_to_marker_contents() {
+        let dir = tempfile::TempDir::new().expect("tempdir");
+        let paths = AppPaths::under(dir.path());
+        let path = dir.path().join(MARKER_FILE);
+
+        fs::write(&path, "glm-work").expect("write marker");
+        let marker = read(&path).expect("marker");
+        assert!(!is_allowed(&paths, &marker).expect("check"));
+        allow(&paths, &marker).expect("allow");
+        assert!(is_allowed(&paths, &marker).expect("check"));
+
+        fs::write(
+            &path,
+            r#"{"preset": "glm-work", "base_url": "https://attacker.example"}"#,
+        )
+        .expect("write marker");
+        let changed = read(&path).expect("marker");
+        assert_eq!(changed.spec.sensitive_fields(), ["base_url"]);
+        assert!(!is_allowed(&paths, &changed).expect("check"));
+
+        assert!(deny(&paths, &path).expect("deny"));
+        assert!(!deny(&paths, &path).expect("deny again"));
+    }
+
+    #[test]
+    fn discover_finds_nearest_marker_upwards() {
+        let dir = tempfile::TempDir::new().expect("tempdir");
+        let nested = dir.path().join("client-a").join("src");
+        fs::create_dir_all(&nested).expect("create dirs");
+        fs::write(dir.path().join(MARKER_FILE), "outer").expect("write marker");
+        fs::write(dir.path().join("client-a").join(MARKER_FILE), "inner").expect("write marker");
+
+        let marker = discover(&nested).expect("discover").expect("marker");
+        assert_eq!(marker.spec.preset, "inner");
+        assert_eq!(marker.path, dir.path().join("client-a").join(MARKER_FILE));
+    }
+}
@@ -1,13 +1,55 @@
-use std::{env, path::PathBuf};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     errors::{AppError, Result},
     fsutil,
 };
 
+/// Which Claude Code settings file a switch targets. Claude Code gives `local`
+/// precedence over `project`, and `project` over `user`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+#[value(rename_all = "lower")]
+pub enum Scope {
+    /// `~/.claude/settings.json`, shared by every project.
+    #[default]
+    User,
+    /// `<project>/.claude/settings.json`, usually committed with the repository.
+    Project,
+    /// `<project>/.claude/settings.local.json`, personal and git-ignored.
+    Local,
+}
+
+impl Scope {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::User => "user",
+            Self::Project => "project",
+            Self::Local => "local",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct AppPaths {
     pub config_path: PathBuf,
+    pub state_path: PathBuf,
+    pub backups_dir: PathBuf,
+    pub journal_path: PathBuf,
+    pub history_path: PathBuf,
+    pub vault_path: PathBuf,
+    /// Marker files approved with `ccswitcher allow`, with their content hashes.
+    pub allowed_markers_path: PathBuf,
+    /// Owner-only directory under `$XDG_RUNTIME_DIR` holding the cached vault
+    /// key; `None` when there is no runtime directory, and the key is not cached.
+    pub session_dir: Option<PathBuf>,
+    pub providers_dir: PathBuf,
     pub claude_home: PathBuf,
     pub settings_path: PathBuf,
     pub settings_local_path: PathBuf,
@@ -18,19 +60,143 @@ impl AppPaths {
         let ccswitcher_home = resolve_home_path("CCSWITCHER_HOME", ".claudecode-switcher")?;
         let claude_home = resolve_home_path("CLAUDE_HOME", ".claude")?;
         let config_path = ccswitcher_home.join("config.json");
+        let state_path = ccswitcher_home.join("state.json");
+        let backups_dir = ccswitcher_home.join("backups");
+        let journal_path = ccswitcher_home.join("journal.json");
+        let history_path = ccswitcher_home.join("history.jsonl");
+        let vault_path = ccswitcher_home.join("vault.json");
+        let allowed_markers_path = ccswitcher_home.join("allowed-markers.json");
+        let session_dir = env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .map(|dir| dir.join("ccswitcher"));
+        let providers_dir = ccswitcher_home.join("providers");
         let settings_path = claude_home.join("settings.json");
         let settings_local_path = claude_home.join("settings.local.json");
 
-        fsutil::ensure_directory(&ccswitcher_home)?;
+        fsutil::ensure_private_directory(&ccswitcher_home)?;
         fsutil::ensure_directory(&claude_home)?;
 
         Ok(Self {
             config_path,
+            state_path,
+            backups_dir,
+            journal_path,
+            history_path,
+            vault_path,
+            allowed_markers_path,
+            session_dir,
+            providers_dir,
             claude_home,
             settings_path,
             settings_local_path,
         })
     }
+
+    /// Layout used by unit tests: the ccswitcher home at `root/ccswitcher` and
+    /// the Claude home at `root/claude`. Nothing is created on disk.
+    #[cfg(test)]
+    pub fn under(root: &Path) -> Self {
+        let ccswitcher_home = root.join("ccswitcher");
+        let claude_home = root.join("claude");
+        Self {
+            config_path: ccswitcher_home.join("config.json"),
+            state_path: ccswitcher_home.join("state.json"),
+            backups_dir: ccswitcher_home.join("backups"),
+            journal_path: ccswitcher_home.join("journal.json"),
+            history_path: ccswitcher_home.join("history.jsonl"),
+            vault_path: ccswitcher_home.join("vault.json"),
+            allowed_markers_path: ccswitcher_home.join("allowed-markers.json"),
+            session_dir: Some(root.join("runtime").join("ccswitcher")),
+            providers_dir: ccswitcher_home.join("providers"),
+            settings_path: claude_home.join("settings.json"),
+            settings_local_path: claude_home.join("settings.local.json"),
+            claude_home,
+        }
+    }
+
+    /// Paths targeting `scope`'s settings file. For project scopes the project
+    /// root is found by walking up from the current directory; state and
+    /// backups for it are kept under `scopes/` in the ccswitcher home.
+    pub fn scoped(&self, scope: Scope) -> Result<Self> {
+        if scope == Scope::User {
+            return Ok(self.clone());
+        }
+        let cwd = env::current_dir().map_err(|err| AppError::io(".", err))?;
+        let root = self
+            .project_root(&cwd)
+            .ok_or(AppError::ProjectNotFound { start: cwd })?;
+
+        let claude_home = root.join(".claude");
+        let settings_local_path = claude_home.join("settings.local.json");
+        let settings_path = match scope {
+            Scope::Local => settings_local_path.clone(),
+            _ => claude_home.join("settings.json"),
+        };
+        let ccswitcher_home = self.config_path.parent().unwrap_or(Path::new("."));
+        let scope_dir = ccswitcher_home
+            .join("scopes")
+            .join(format!("{:016x}", fsutil::path_hash(&settings_path)));
+
+        Ok(Self {
+            state_path: scope_dir.join("state.json"),
+            backups_dir: scope_dir.join("backups"),
+            journal_path: scope_dir.join("journal.json"),
+            claude_home,
+            settings_path,
+            settings_local_path,
+            ..self.clone()
+        })
+    }
+
+    /// Whether these paths target a project or local settings file, whose state
+    /// is kept under `scopes/` instead of next to `config.json`.
+    pub fn is_scoped(&self) -> bool {
+        self.state_path.parent() != self.config_path.parent()
+    }
+
+    /// Paths of every project or local scope with state recorded under
+    /// `scopes/`. Only the state, journal and backup paths point into the
+    /// scope; which settings file it belongs to is not recorded.
+    pub fn recorded_scopes(&self) -> Result<Vec<Self>> {
+        let ccswitcher_home = self.config_path.parent().unwrap_or(Path::new("."));
+        let scopes_dir = ccswitcher_home.join("scopes");
+        if !scopes_dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut scopes = Vec::new();
+        for entry in fs::read_dir(&scopes_dir).map_err(|err| AppError::io(&scopes_dir, err))? {
+            let scope_dir = entry.map_err(|err| AppError::io(&scopes_dir, err))?.path();
+            if !scope_dir.is_dir() {
+                continue;
+            }
+            scopes.push(Self {
+                state_path: scope_dir.join("state.json"),
+                backups_dir: scope_dir.join("backups"),
+                journal_path: scope_dir.join("journal.json"),
+                ..self.clone()
+            });
+        }
+        Ok(scopes)
+    }
+
+    /// The nearest ancestor of `start` holding a `.claude` directory or `.git`,
+    /// skipping the directory whose `.claude` is the user-level Claude home.
+    pub fn project_root(&self, start: &Path) -> Option<PathBuf> {
+        start
+            .ancestors()
+            .find(|dir| {
+                let claude_dir = dir.join(".claude");
+                (claude_dir.is_dir() && claude_dir != self.claude_home) || dir.join(".git").exists()
+            })
+            .map(Path::to_path_buf)
+    }
+
+    /// Where `ccswitcher install` puts the `/switchmodel` slash command.
+    pub fn switchmodel_command_path(&self) -> PathBuf {
+        self.claude_home.join("commands").join("switchmodel.md")
+    }
 }
 
 fn resolve_home_path(override_var: &str, default_suffix: &str) -> Result<PathBuf> {
@@ -1,22 +1,381 @@
-use std::fmt::{Display, Formatter};
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter},
+    fs,
+    path::Path,
+    str::FromStr,
+};
 
-use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
-#[serde(rename_all = "lowercase")]
-#[value(rename_all = "lower")]
+use crate::{
+    config,
+    errors::{AppError, Result},
+    paths::AppPaths,
+    settings::OVERRIDE_ENV_KEYS,
+};
+
+/// A preset's provider: a built-in catalog entry, `custom`, or the name of a
+/// template in the ccswitcher home's `providers/` directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
 pub enum ProviderKind {
     Glm,
+    Deepseek,
+    Kimi,
+    Qwen,
+    Minimax,
+    Openrouter,
+    /// An Anthropic-compatible proxy on this machine, such as LiteLLM.
+    Local,
     #[default]
     Custom,
+    /// A template loaded from `providers/<name>.json`.
+    User(String),
+}
+
+/// Defaults a provider fills in for `add` when the options are omitted.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProviderTemplate {
+    #[serde(default)]
+    pub label: String,
+    #[serde(default)]
+    pub base_url: Option<String>,
+    #[serde(default)]
+    pub haiku_model: Option<String>,
+    #[serde(default)]
+    pub sonnet_model: Option<String>,
+    #[serde(default)]
+    pub opus_model: Option<String>,
+    #[serde(default)]
+    pub extra_env: BTreeMap<String, String>,
+    /// Fields `add` must be given explicitly: `base_url`, `haiku`, `sonnet`,
+    /// `opus`, or an extra env key expected via `--env`.
+    #[serde(default)]
+    pub required: Vec<String>,
+    /// Where to get a token, shown by `providers` and the add wizard.
+    #[serde(default)]
+    pub token_hint: Option<String>,
+}
+
+impl ProviderKind {
+    /// Catalog entries shipped with ccswitcher, in listing order.
+    pub const BUILTIN: [ProviderKind; 7] = [
+        Self::Glm,
+        Self::Deepseek,
+        Self::Kimi,
+        Self::Qwen,
+        Self::Minimax,
+        Self::Openrouter,
+        Self::Local,
+    ];
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Glm => "glm",
+            Self::Deepseek => "deepseek",
+            Self::Kimi => "kimi",
+            Self::Qwen => "qwen",
+            Self::Minimax => "minimax",
+            Self::Openrouter => "openrouter",
+            Self::Local => "local",
+            Self::Custom => "custom",
+            Self::User(name) => name,
+        }
+    }
+
+    /// Built-in defaults; `None` for `custom` and user templates.
+    fn builtin_template(&self) -> Option<ProviderTemplate> {
+        let (label, base_url, [haiku, sonnet, opus], extra_env): (_, _, _, &[(&str, &str)]) =
+            match self {
+                Self::Glm => (
+                    "Zhipu GLM",
+                    "https://open.bigmodel.cn/api/anthropic",
+                    ["glm-4.5-air", "glm-4.7", "glm-4.7"],
+                    &[],
+                ),
+                Self::Deepseek => (
+                    "DeepSeek",
+                    "https://api.deepseek.com/anthropic",
+                    ["deepseek-chat", "deepseek-chat", "deepseek-chat"],
+                    &[("ANTHROPIC_SMALL_FAST_MODEL", "deepseek-chat")],
+                ),
+                Self::Kimi => (
+                    "Kimi (Moonshot)",
+                    "https://api.moonshot.cn/anthropic",
+                    [
+                        "kimi-k2-turbo-preview",
+                        "kimi-k2-0905-preview",
+                        "kimi-k2-0905-preview",
+                    ],
+                    &[("ANTHROPIC_SMALL_FAST_MODEL", "kimi-k2-turbo-preview")],
+                ),
+                Self::Qwen => (
+                    "Qwen (DashScope)",
+                    "https://dashscope.aliyuncs.com/apps/anthropic",
+                    ["qwen3-coder-flash", "qwen3-coder-plus", "qwen3-max"],
+                    &[],
+                ),
+                Self::Minimax => (
+                    "MiniMax",
+                    "https://api.minimax.io/anthropic",
+                    ["MiniMax-M2", "MiniMax-M2", "MiniMax-M2"],
+                    &[],
+                ),
+                Self::Openrouter => (
+                    "OpenRouter",
+                    "https://openrouter.ai/api",
+                    [
+                        "anthropic/claude-haiku-4.5",
+                        "anthropic/claude-sonnet-4.5",
+                        "anthropic/claude-opus-4.1",
+                    ],
+                    &[],
+                ),
+                Self::Local => (
+                    "Local proxy",
+                    "http://127.0.0.1:4000",
+                    ["claude-haiku-4-5", "claude-sonnet-4-5", "claude-opus-4-1"],
+                    &[("NO_PROXY", "127.0.0.1,localhost")],
+                ),
+                Self::Custom | Self::User(_) => return None,
+            };
+        Some(ProviderTemplate {
+            label: label.to_owned(),
+            base_url: Some(base_url.to_owned()),
+            haiku_model: Some(haiku.to_owned()),
+            sonnet_model: Some(sonnet.to_owned()),
+            opus_model: Some(opus.to_owned()),
+            extra_env: extra_env
+                .iter()
+                .map(|(key, value)| ((*key).to_owned(), (*value).to_owned()))
+                .collect(),
+            required: Vec::new(),
+            token_hint: None,
+        })
+    }
+}
+
+impl FromStr for ProviderKind {
+    type Err = String;
+
+    /// Built-in names and aliases are matched case-insensitively; any other
+    /// lowercase name refers to a user template.
+    fn from_str(raw: &str) -> std::result::Result<Self, Self::Err> {
+        let name = raw.trim().to_ascii_lowercase();
+        let kind = match name.as_str() {
+            "glm" => Self::Glm,
+            "deepseek" => Self::Deepseek,
+            "kimi" | "moonshot" => Self::Kimi,
+            "qwen" | "dashscope" => Self::Qwen,
+            "minimax" => Self::Minimax,
+            "openrouter" => Self::Openrouter,
+            "local" => Self::Local,
+            "custom" => Self::Custom,
+            _ if is_template_name(&name) => Self::User(name),
+            _ => {
+                return Err(format!(
+                    "invalid provider name '{raw}' (use letters, digits, '-' or '_')"
+                ));
+            }
+        };
+        Ok(kind)
+    }
+}
+
+impl TryFrom<String> for ProviderKind {
+    type Error = String;
+
+    fn try_from(raw: String) -> std::result::Result<Self, Self::Error> {
+        raw.parse()
+    }
+}
+
+impl From<ProviderKind> for String {
+    fn from(kind: ProviderKind) -> Self {
+        kind.name().to_owned()
+    }
 }
 
 impl Display for ProviderKind {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ProviderKind::Glm => write!(f, "glm"),
-            ProviderKind::Custom => write!(f, "custom"),
+        f.write_str(self.name())
+    }
+}
+
+fn is_template_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+/// Built-in providers followed by the user templates on disk.
+pub struct Catalog {
+    entries: Vec<(ProviderKind, ProviderTemplate)>,
+    /// Template files that could not be used; they are left out of `entries`.
+    broken: Vec<AppError>,
+}
+
+impl Catalog {
+    /// Reads every `providers/*.json` template for listing. A template that
+    /// cannot be read, shadows a built-in name, or sets a reserved env key is
+    /// skipped and reported by [`Catalog::broken`], so it cannot hide the rest.
+    pub fn load(paths: &AppPaths) -> Result<Self> {
+        let mut catalog = Self {
+            entries: ProviderKind::BUILTIN
+                .into_iter()
+                .filter_map(|kind| kind.builtin_template().map(|template| (kind, template)))
+                .collect(),
+            broken: Vec::new(),
+        };
+        if !paths.providers_dir.is_dir() {
+            return Ok(catalog);
         }
+
+        let dir = &paths.providers_dir;
+        let mut files: Vec<_> = fs::read_dir(dir)
+            .map_err(|err| AppError::io(dir, err))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        files.sort();
+        for path in files {
+            match read_template(&path) {
+                Ok(entry) => catalog.entries.push(entry),
+                Err(err) => catalog.broken.push(err),
+            }
+        }
+        Ok(catalog)
+    }
+
+    pub fn entries(&self) -> &[(ProviderKind, ProviderTemplate)] {
+        &self.entries
+    }
+
+    pub fn broken(&self) -> &[AppError] {
+        &self.broken
+    }
+
+    /// Defaults for `kind`: `None` for `custom`, an error for an unknown name.
+    pub fn template(&self, kind: &ProviderKind) -> Result<Option<&ProviderTemplate>> {
+        if *kind == ProviderKind::Custom {
+            return Ok(None);
+        }
+        self.entries
+            .iter()
+            .find(|(entry, _)| entry == kind)
+            .map(|(_, template)| Some(template))
+            .ok_or_else(|| AppError::UnknownProvider(kind.to_string()))
+    }
+}
+
+/// Defaults for `kind`, reading no template file but its own: `None` for
+/// `custom`, an error for an unknown name or a broken template.
+pub fn template(paths: &AppPaths, kind: &ProviderKind) -> Result<Option<ProviderTemplate>> {
+    match kind {
+        ProviderKind::Custom => Ok(None),
+        ProviderKind::User(name) => {
+            let path = paths.providers_dir.join(format!("{name}.json"));
+            if !path.is_file() {
+                return Err(AppError::UnknownProvider(name.clone()));
+            }
+            read_template(&path).map(|(_, template)| Some(template))
+        }
+        builtin => Ok(builtin.builtin_template()),
+    }
+}
+
+fn read_template(path: &Path) -> Result<(ProviderKind, ProviderTemplate)> {
+    let invalid = |reason: String| AppError::InvalidProviderTemplate {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+    let kind = match stem.parse::<ProviderKind>() {
+        Ok(ProviderKind::User(name)) if name == stem => ProviderKind::User(name),
+        Ok(ProviderKind::User(_)) | Err(_) => {
+            return Err(invalid(
+                "file name must be lowercase letters, digits, '-' or '_'".to_owned(),
+            ));
+        }
+        Ok(_) => {
+            return Err(invalid(format!("'{stem}' is a built-in provider name")));
+        }
+    };
+
+    let raw = fs::read_to_string(path).map_err(|err| AppError::io(path, err))?;
+    let mut template: ProviderTemplate =
+        serde_json::from_str(&raw).map_err(|err| invalid(err.to_string()))?;
+    if let Some(key) = template
+        .extra_env
+        .keys()
+        .find(|key| OVERRIDE_ENV_KEYS.contains(&key.as_str()))
+    {
+        return Err(invalid(format!(
+            "'{key}' is managed by a dedicated preset field"
+        )));
+    }
+    if let Some(key) = template
+        .extra_env
+        .keys()
+        .find(|key| !config::is_env_key(key))
+    {
+        return Err(invalid(format!("'{key}' is not a valid env key")));
+    }
+    if template.label.trim().is_empty() {
+        template.label = stem.to_owned();
+    }
+    Ok((kind, template))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_builtin_entry_has_complete_defaults() {
+        for kind in ProviderKind::BUILTIN {
+            let template = kind.builtin_template().expect("built-in template");
+            assert!(
+                template
+                    .base_url
+                    .as_deref()
+                    .is_some_and(|url| url.starts_with("http")),
+                "{kind}"
+            );
+            for model in [
+                &template.haiku_model,
+                &template.sonnet_model,
+                &template.opus_model,
+            ] {
+                assert!(model.as_deref().is_some_and(|m| !m.is_empty()), "{kind}");
+            }
+            for key in template.extra_env.keys() {
+                assert!(
+                    !OVERRIDE_ENV_KEYS.contains(&key.as_str()),
+                    "{kind} sets {key}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn names_parse_to_builtins_aliases_or_user_templates() {
+        assert_eq!("Moonshot".parse(), Ok(ProviderKind::Kimi));
+        assert_eq!(
+            "our-gateway".parse(),
+            Ok(ProviderKind::User("our-gateway".to_owned()))
+        );
+        assert!("our gateway".parse::<ProviderKind>().is_err());
+
+        let parsed: ProviderKind = serde_json::from_str("\"dashscope\"").expect("alias");
+        assert_eq!(parsed, ProviderKind::Qwen);
+        let user = serde_json::to_string(&ProviderKind::User("gw".to_owned())).expect("serialize");
+        assert_eq!(user, "\"gw\"");
     }
 }
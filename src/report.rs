@@ -0,0 +1,68 @@
+use std::io::Write;
+
+use serde_json::{Value, json};
+
+use crate::{
+    config::Preset,
+    errors::{AppError, Result},
+    settings,
+    token::TokenSource,
+};
+
+pub fn write_json(out: &mut dyn Write, value: &Value) -> Result<()> {
+    let body = serde_json::to_string_pretty(value).map_err(std::io::Error::other);
+    let body = body.map_err(AppError::output)?;
+    writeln!(out, "{body}").map_err(AppError::output)
+}
+
+/// Success envelope shared by every mutating command in JSON mode.
+pub fn ok(operation: &str, fields: Value) -> Value {
+    let mut body = json!({ "ok": true, "operation": operation });
+    if let (Some(body), Value::Object(fields)) = (body.as_object_mut(), fields) {
+        body.extend(fields);
+    }
+    body
+}
+
+pub fn error(err: &AppError) -> Value {
+    let mut body = json!({
+        "ok": false,
+        "error": {
+            "code": err.code(),
+            "exit_code": err.exit_code(),
+            "message": err.to_string(),
+        }
+    });
+    if let (Some(body), Some(Value::Object(fields))) = (body.as_object_mut(), err.report()) {
+        for (key, value) in fields {
+            body.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+    }
+    body
+}
+
+/// Preset as JSON with plain auth tokens and credential-like extra env values masked.
+/// Token references (`env:`, `file:`, `cmd:`) are kept since they hold no secret.
+pub fn masked_preset(preset: &Preset) -> Value {
+    let mut value = serde_json::to_value(preset).unwrap_or_else(|_| json!({}));
+    if let Some(obj) = value.as_object_mut() {
+        let source = TokenSource::parse(&preset.auth_token);
+        if matches!(source, TokenSource::Plain(token) if !token.is_empty()) {
+            obj.insert("auth_token".to_owned(), json!("****"));
+        }
+        obj.insert("token_source".to_owned(), json!(source.kind()));
+        if let Some(proxy) = obj
+            .get_mut("network")
+            .and_then(|network| network.get_mut("http_proxy"))
+            && let Some(url) = proxy.as_str()
+        {
+            *proxy = Value::String(settings::mask_url_userinfo(url));
+        }
+        if let Some(extra) = obj.get_mut("extra_env").and_then(Value::as_object_mut) {
+            for (key, value) in extra.iter_mut() {
+                *value = Value::String(settings::display_env_value(key, value));
+            }
+        }
+    }
+    value
+}
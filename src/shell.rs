@@ -0,0 +1,118 @@
+use clap::ValueEnum;
+
+/// Output syntax for `ccswitcher env`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+#[value(rename_all = "lower")]
+pub enum Shell {
+    #[default]
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+    /// `KEY=value` lines for `.env` files and `--env-file` options.
+    Dotenv,
+}
+
+impl Shell {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Bash => "bash",
+            Self::Zsh => "zsh",
+            Self::Fish => "fish",
+            Self::Powershell => "powershell",
+            Self::Dotenv => "dotenv",
+        }
+    }
+
+    /// A line that sets `key` to `value` literally, without expansion.
+    pub fn set_line(self, key: &str, value: &str) -> String {
+        match self {
+            Self::Bash | Self::Zsh => format!("export {key}={}", posix_quote(value)),
+            Self::Fish => format!("set -gx {key} {}", fish_quote(value)),
+            Self::Powershell => format!("$env:{key} = '{}'", value.replace('\'', "''")),
+            Self::Dotenv => format!("{key}={}", dotenv_quote(value)),
+        }
+    }
+
+    /// A line that removes `key`, or `None` where the format has no way to unset.
+    pub fn unset_line(self, key: &str) -> Option<String> {
+        match self {
+            Self::Bash | Self::Zsh => Some(format!("unset {key}")),
+            Self::Fish => Some(format!("set -e {key}")),
+            Self::Powershell => Some(format!(
+                "Remove-Item Env:{key} -ErrorAction SilentlyContinue"
+            )),
+            Self::Dotenv => None,
+        }
+    }
+}
+
+/// Shells `ccswitcher hook` can generate a directory-change hook for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[value(rename_all = "lower")]
+pub enum HookShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl HookShell {
+    /// Script that runs `ccswitcher auto --quiet` whenever the working directory changes.
+    pub fn script(self) -> &'static str {
+        match self {
+            Self::Bash => include_str!("../templates/hooks/bash.sh"),
+            Self::Zsh => include_str!("../templates/hooks/zsh.sh"),
+            Self::Fish => include_str!("../templates/hooks/fish.fish"),
+        }
+    }
+}
+
+fn posix_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+fn fish_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', r"\\").replace('\'', r"\'"))
+}
+
+/// Single quotes keep values literal in common dotenv loaders; values that
+/// cannot be single-quoted fall back to escaped double quotes.
+fn dotenv_quote(value: &str) -> String {
+    if !value.contains(['\'', '\n']) {
+        return format!("'{value}'");
+    }
+    let escaped = value
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n")
+        .replace('$', r"\$");
+    format!("\"{escaped}\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_lines_quote_values_literally_per_shell() {
+        let value = "it's $HOME";
+        assert_eq!(
+            Shell::Bash.set_line("K", value),
+            r"export K='it'\''s $HOME'"
+        );
+        assert_eq!(Shell::Fish.set_line("K", value), r"set -gx K 'it\'s $HOME'");
+        assert_eq!(
+            Shell::Powershell.set_line("K", value),
+            "$env:K = 'it''s $HOME'"
+        );
+        assert_eq!(Shell::Dotenv.set_line("K", value), r#"K="it's \$HOME""#);
+        assert_eq!(Shell::Dotenv.set_line("K", "plain"), "K='plain'");
+    }
+
+    #[test]
+    fn unset_lines_exist_for_shells_only() {
+        assert_eq!(Shell::Zsh.unset_line("K").as_deref(), Some("unset K"));
+        assert_eq!(Shell::Fish.unset_line("K").as_deref(), Some("set -e K"));
+        assert_eq!(Shell::Dotenv.unset_line("K"), None);
+    }
+}
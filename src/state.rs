@@ -0,0 +1,51 @@
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    errors::{AppError, Result},
+    fsutil,
+    paths::AppPaths,
+};
+
+/// Bookkeeping about what ccswitcher itself wrote into `settings.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SwitcherState {
+    /// Env keys currently written by ccswitcher, mapped to the value they held
+    /// before ccswitcher first took them over (`None` when the key was absent).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub owned_env: BTreeMap<String, Option<Value>>,
+    /// Preset applied to a project or local settings file. The user-level
+    /// active preset lives in `config.json` instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_preset: Option<String>,
+    /// `.ccswitcher` marker that chose the applied preset via `ccswitcher auto`.
+    /// Cleared by any other write to the settings file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub marker: Option<PathBuf>,
+}
+
+pub fn load(paths: &AppPaths) -> Result<SwitcherState> {
+    if !paths.state_path.exists() {
+        return Ok(SwitcherState::default());
+    }
+
+    let raw = fs::read_to_string(&paths.state_path)
+        .map_err(|err| AppError::io(&paths.state_path, err))?;
+    serde_json::from_str(&raw).map_err(|err| AppError::json(&paths.state_path, err))
+}
+
+pub fn save(paths: &AppPaths, state: &SwitcherState) -> Result<()> {
+    fsutil::write_json_atomic(&paths.state_path, state)
+}
+
+/// Points a recorded `active_preset` of `old` at `new`.
+pub fn rename_preset(paths: &AppPaths, old: &str, new: &str) -> Result<()> {
+    let mut switcher_state = load(paths)?;
+    if switcher_state.active_preset.as_deref() != Some(old) {
+        return Ok(());
+    }
+    switcher_state.active_preset = Some(new.to_owned());
+    save(paths, &switcher_state)
+}
@@ -0,0 +1,175 @@
+use std::{env, fs, path::PathBuf, process::Command};
+
+use crate::{
+    errors::{AppError, Result},
+    paths::AppPaths,
+    vault,
+};
+
+/// Where a preset's `auth_token` value comes from.
+///
+/// Besides a plain-text token, `auth_token` may hold an indirection that is
+/// only resolved when the preset is applied: `env:VAR_NAME`,
+/// `file:/path/to/token`, `cmd:<shell command>`, or `vault:<entry>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenSource<'a> {
+    Plain(&'a str),
+    Env(&'a str),
+    File(&'a str),
+    Cmd(&'a str),
+    Vault(&'a str),
+}
+
+impl<'a> TokenSource<'a> {
+    pub fn parse(raw: &'a str) -> Self {
+        let raw = raw.trim();
+        if let Some(var) = raw.strip_prefix("env:") {
+            Self::Env(var.trim())
+        } else if let Some(path) = raw.strip_prefix("file:") {
+            Self::File(path.trim())
+        } else if let Some(command) = raw.strip_prefix("cmd:") {
+            Self::Cmd(command.trim())
+        } else if let Some(entry) = raw.strip_prefix("vault:") {
+            Self::Vault(entry.trim())
+        } else {
+            Self::Plain(raw)
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Plain(_) => "plain",
+            Self::Env(_) => "env",
+            Self::File(_) => "file",
+            Self::Cmd(_) => "cmd",
+            Self::Vault(_) => "vault",
+        }
+    }
+
+    /// The token itself for plain sources, otherwise the variable, path or command.
+    pub fn target(&self) -> &'a str {
+        match self {
+            Self::Plain(value)
+            | Self::Env(value)
+            | Self::File(value)
+            | Self::Cmd(value)
+            | Self::Vault(value) => value,
+        }
+    }
+
+    /// Human-readable description that never contains a plain-text token.
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Plain(_) => "plain text (****)".to_owned(),
+            other => format!("{}:{}", other.kind(), other.target()),
+        }
+    }
+
+    pub fn resolve(&self, paths: &AppPaths) -> Result<String> {
+        let token = match self {
+            Self::Plain(value) => return Ok((*value).to_owned()),
+            Self::Vault(entry) => vault::reveal(paths, entry)?
+                .ok_or_else(|| self.unavailable("no such vault entry"))?,
+            Self::Env(var) => env::var(var).map_err(|_| self.unavailable("variable is not set"))?,
+            Self::File(path) => {
+                let path = expand_home(path);
+                fs::read_to_string(&path).map_err(|err| self.unavailable(&err.to_string()))?
+            }
+            Self::Cmd(command) => {
+                run_command(command).map_err(|reason| self.unavailable(&reason))?
+            }
+        };
+        let token = token.trim();
+        if token.is_empty() {
+            return Err(self.unavailable("token is empty"));
+        }
+        Ok(token.to_owned())
+    }
+
+    fn unavailable(&self, reason: &str) -> AppError {
+        AppError::TokenUnavailable {
+            origin: self.describe(),
+            reason: reason.to_owned(),
+        }
+    }
+}
+
+/// Resolves a raw `auth_token` value into the token written to `settings.json`.
+pub fn resolve(paths: &AppPaths, raw: &str) -> Result<String> {
+    TokenSource::parse(raw).resolve(paths)
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn run_command(command: &str) -> std::result::Result<String, String> {
+    let output = if cfg!(windows) {
+        Command::new("cmd").args(["/C", command]).output()
+    } else {
+        Command::new("sh").args(["-c", command]).output()
+    }
+    .map_err(|err| err.to_string())?;
+
+    if !output.status.success() {
+        return Err(format!("command exited with {}", output.status));
+    }
+    String::from_utf8(output.stdout).map_err(|_| "command output is not UTF-8".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_indirections_and_plain_tokens() {
+        assert_eq!(
+            TokenSource::parse("env:GLM_TOKEN"),
+            TokenSource::Env("GLM_TOKEN")
+        );
+        assert_eq!(
+            TokenSource::parse("file:/tmp/token"),
+            TokenSource::File("/tmp/token")
+        );
+        assert_eq!(
+            TokenSource::parse("cmd:pass show glm"),
+            TokenSource::Cmd("pass show glm")
+        );
+        assert_eq!(TokenSource::parse(" secret "), TokenSource::Plain("secret"));
+        assert_eq!(TokenSource::parse("secret").describe(), "plain text (****)");
+        assert_eq!(
+            TokenSource::parse("env:GLM_TOKEN").describe(),
+            "env:GLM_TOKEN"
+        );
+        assert_eq!(TokenSource::parse("vault:glm"), TokenSource::Vault("glm"));
+    }
+
+    #[test]
+    fn resolves_file_and_command_sources() {
+        let tmp = tempfile::TempDir::new().expect("tempdir");
+        let paths = AppPaths::under(tmp.path());
+        let token_path = tmp.path().join("token");
+        fs::write(&token_path, "file-secret\n").expect("write token");
+
+        let raw = format!("file:{}", token_path.display());
+        assert_eq!(resolve(&paths, &raw).expect("file token"), "file-secret");
+        assert!(matches!(
+            resolve(&paths, "file:/definitely/missing/token"),
+            Err(AppError::TokenUnavailable { .. })
+        ));
+
+        if cfg!(unix) {
+            assert_eq!(
+                resolve(&paths, "cmd:echo cmd-secret").expect("cmd token"),
+                "cmd-secret"
+            );
+            assert!(matches!(
+                resolve(&paths, "cmd:exit 3"),
+                Err(AppError::TokenUnavailable { .. })
+            ));
+        }
+    }
+}
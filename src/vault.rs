@@ -0,0 +1,444 @@
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    io::{self, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chacha20poly1305::{
+    ChaCha20Poly1305, KeyInit, Nonce,
+    aead::{Aead, AeadCore, OsRng, rand_core::RngCore},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clock,
+    errors::{AppError, Result},
+    fsutil,
+    paths::AppPaths,
+};
+
+pub const PASSPHRASE_ENV: &str = "CCSWITCHER_PASSPHRASE";
+
+const CURRENT_VAULT_VERSION: u32 = 1;
+/// Known plaintext sealed at init time so a wrong passphrase is detected up front.
+const CHECK_PLAINTEXT: &[u8] = b"ccswitcher-vault";
+/// How long an unlocked key stays cached before the passphrase is asked for again.
+const SESSION_KEY_TTL_MS: u64 = 8 * 60 * 60 * 1000;
+
+/// Encrypted token store kept next to `config.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Vault {
+    pub vault_version: u32,
+    pub kdf: KdfParams,
+    pub check: Sealed,
+    /// Sealed tokens by entry id. New entries get random ids, so presets never
+    /// share an entry after a rename; older vaults keyed them by preset name.
+    #[serde(default)]
+    pub entries: BTreeMap<String, Sealed>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub algorithm: String,
+    pub salt: String,
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sealed {
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+/// A vault together with the key derived from its passphrase.
+pub struct UnlockedVault {
+    vault: Vault,
+    key: [u8; 32],
+}
+
+impl UnlockedVault {
+    /// Encrypts `token` into a new entry and returns the entry's id.
+    pub fn seal(&mut self, token: &str) -> String {
+        let entry = loop {
+            let mut id = [0u8; 8];
+            OsRng.fill_bytes(&mut id);
+            let id: String = id.iter().map(|byte| format!("{byte:02x}")).collect();
+            if !self.vault.entries.contains_key(&id) {
+                break id;
+            }
+        };
+        let sealed = seal(&self.key, token.as_bytes());
+        self.vault.entries.insert(entry.clone(), sealed);
+        entry
+    }
+
+    pub fn open(&self, paths: &AppPaths, entry: &str) -> Result<Option<String>> {
+        let Some(sealed) = self.vault.entries.get(entry) else {
+            return Ok(None);
+        };
+        let plain = open(&self.key, sealed).ok_or_else(|| corrupt(paths))?;
+        String::from_utf8(plain)
+            .map(Some)
+            .map_err(|_| corrupt(paths))
+    }
+
+    pub fn save(&self, paths: &AppPaths) -> Result<()> {
+        fsutil::write_json_atomic(&paths.vault_path, &self.vault)
+    }
+}
+
+pub fn exists(paths: &AppPaths) -> bool {
+    paths.vault_path.exists()
+}
+
+/// Creates an empty vault and caches its key for the current session.
+pub fn init(paths: &AppPaths) -> Result<UnlockedVault> {
+    if exists(paths) {
+        return Err(AppError::VaultAlreadyInitialized);
+    }
+    let passphrase = new_passphrase()?;
+    let unlocked = create(paths, &passphrase)?;
+    unlocked.save(paths)?;
+    remember_key(paths, &unlocked.key)?;
+    Ok(unlocked)
+}
+
+/// Unlocks the vault using `CCSWITCHER_PASSPHRASE`, the session key cache, or a prompt.
+pub fn unlock(paths: &AppPaths) -> Result<UnlockedVault> {
+    let vault = load(paths)?;
+
+    if let Ok(passphrase) = env::var(PASSPHRASE_ENV) {
+        return unlock_with(paths, vault, &passphrase);
+    }
+
+    if let Some(key) = cached_key(paths)
+        && verify(&vault, &key).is_ok()
+    {
+        return Ok(UnlockedVault { vault, key });
+    }
+
+    if !std::io::stdin().is_terminal() {
+        return Err(AppError::VaultLocked);
+    }
+    let passphrase = rpassword::prompt_password("Vault passphrase: ").map_err(AppError::output)?;
+    let unlocked = unlock_with(paths, vault, &passphrase)?;
+    remember_key(paths, &unlocked.key)?;
+    Ok(unlocked)
+}
+
+/// Forgets the cached session key; the next vault access asks for the passphrase again.
+pub fn lock(paths: &AppPaths) -> Result<bool> {
+    let Some(session) = session_key_path(paths) else {
+        return Ok(false);
+    };
+    match fs::remove_file(&session) {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(AppError::io(&session, err)),
+    }
+}
+
+/// Decrypts a single vault entry, or `None` when the entry does not exist.
+pub fn reveal(paths: &AppPaths, entry: &str) -> Result<Option<String>> {
+    unlock(paths)?.open(paths, entry)
+}
+
+/// A new, empty vault protected by `passphrase`. Nothing is written yet.
+fn create(paths: &AppPaths, passphrase: &str) -> Result<UnlockedVault> {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let params = Params::default();
+    let kdf = KdfParams {
+        algorithm: "argon2id".to_owned(),
+        salt: STANDARD.encode(salt),
+        memory_kib: params.m_cost(),
+        iterations: params.t_cost(),
+        parallelism: params.p_cost(),
+    };
+    let key = derive_key(paths, &kdf, passphrase)?;
+    let vault = Vault {
+        vault_version: CURRENT_VAULT_VERSION,
+        kdf,
+        check: seal(&key, CHECK_PLAINTEXT),
+        entries: BTreeMap::new(),
+    };
+    Ok(UnlockedVault { vault, key })
+}
+
+fn unlock_with(paths: &AppPaths, vault: Vault, passphrase: &str) -> Result<UnlockedVault> {
+    let key = derive_key(paths, &vault.kdf, passphrase)?;
+    verify(&vault, &key)?;
+    Ok(UnlockedVault { vault, key })
+}
+
+fn load(paths: &AppPaths) -> Result<Vault> {
+    if !exists(paths) {
+        return Err(AppError::VaultNotInitialized);
+    }
+    let raw = fs::read_to_string(&paths.vault_path)
+        .map_err(|err| AppError::io(&paths.vault_path, err))?;
+    let vault: Vault =
+        serde_json::from_str(&raw).map_err(|err| AppError::json(&paths.vault_path, err))?;
+    if vault.vault_version != CURRENT_VAULT_VERSION || vault.kdf.algorithm != "argon2id" {
+        return Err(corrupt(paths));
+    }
+    Ok(vault)
+}
+
+fn new_passphrase() -> Result<String> {
+    if let Ok(passphrase) = env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    if !std::io::stdin().is_terminal() {
+        return Err(AppError::VaultLocked);
+    }
+    loop {
+        let first =
+            rpassword::prompt_password("New vault passphrase: ").map_err(AppError::output)?;
+        if first.is_empty() {
+            eprintln!("Passphrase cannot be empty.");
+            continue;
+        }
+        let second = rpassword::prompt_password("Repeat passphrase: ").map_err(AppError::output)?;
+        if first == second {
+            return Ok(first);
+        }
+        eprintln!("Passphrases do not match.");
+    }
+}
+
+fn derive_key(paths: &AppPaths, kdf: &KdfParams, passphrase: &str) -> Result<[u8; 32]> {
+    let salt = STANDARD.decode(&kdf.salt).map_err(|_| corrupt(paths))?;
+    let params = Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(32))
+        .map_err(|_| corrupt(paths))?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+        .map_err(|_| corrupt(paths))?;
+    Ok(key)
+}
+
+fn verify(vault: &Vault, key: &[u8; 32]) -> Result<()> {
+    match open(key, &vault.check) {
+        Some(plain) if plain == CHECK_PLAINTEXT => Ok(()),
+        _ => Err(AppError::VaultPassphraseIncorrect),
+    }
+}
+
+fn seal(key: &[u8; 32], plaintext: &[u8]) -> Sealed {
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = ChaCha20Poly1305::new(key.into())
+        .encrypt(&nonce, plaintext)
+        .expect("ChaCha20Poly1305 only rejects plaintexts far larger than a token");
+    Sealed {
+        nonce: STANDARD.encode(nonce),
+        ciphertext: STANDARD.encode(ciphertext),
+    }
+}
+
+fn open(key: &[u8; 32], sealed: &Sealed) -> Option<Vec<u8>> {
+    let nonce = STANDARD.decode(&sealed.nonce).ok()?;
+    let ciphertext = STANDARD.decode(&sealed.ciphertext).ok()?;
+    if nonce.len() != 12 {
+        return None;
+    }
+    ChaCha20Poly1305::new(key.into())
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        .ok()
+}
+
+fn corrupt(paths: &AppPaths) -> AppError {
+    AppError::VaultCorrupt {
+        path: paths.vault_path.clone(),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SessionKey {
+    expires_at_ms: u64,
+    key: String,
+}
+
+/// Cache file for this vault's key. It lives in the per-user runtime directory
+/// (usually a tmpfs), so it never ends up in backups or synced folders.
+fn session_key_path(paths: &AppPaths) -> Option<PathBuf> {
+    let session_dir = paths.session_dir.as_ref()?;
+    Some(session_dir.join(format!(
+        "vault-{:016x}.key",
+        fsutil::path_hash(&paths.vault_path)
+    )))
+}
+
+fn cached_key(paths: &AppPaths) -> Option<[u8; 32]> {
+    if !is_private(paths.session_dir.as_ref()?) {
+        return None;
+    }
+    let session = session_key_path(paths)?;
+    let mut options = fs::OpenOptions::new();
+    options.read(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::custom_flags(&mut options, libc::O_NOFOLLOW);
+    let mut file = options.open(&session).ok()?;
+    if !is_private_file(&file.metadata().ok()?) {
+        return None;
+    }
+    let mut raw = String::new();
+    file.read_to_string(&mut raw).ok()?;
+
+    let cached: SessionKey = serde_json::from_str(&raw).ok()?;
+    if clock::now_millis() >= cached.expires_at_ms {
+        let _ = fs::remove_file(&session);
+        return None;
+    }
+    STANDARD.decode(cached.key).ok()?.try_into().ok()
+}
+
+/// Caches `key` for the session; without a runtime directory nothing is cached.
+fn remember_key(paths: &AppPaths, key: &[u8; 32]) -> Result<()> {
+    let (Some(session_dir), Some(session)) = (&paths.session_dir, session_key_path(paths)) else {
+        return Ok(());
+    };
+    fsutil::ensure_private_directory(session_dir)?;
+    if !is_private(session_dir) {
+        return Err(AppError::io(
+            session_dir,
+            io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "the session directory must be owned by the current user and not accessible to others",
+            ),
+        ));
+    }
+
+    match fs::remove_file(&session) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(AppError::io(&session, err)),
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600).custom_flags(libc::O_NOFOLLOW);
+    }
+    let mut file = options
+        .open(&session)
+        .map_err(|err| AppError::io(&session, err))?;
+    let cached = SessionKey {
+        expires_at_ms: clock::now_millis().saturating_add(SESSION_KEY_TTL_MS),
+        key: STANDARD.encode(key),
+    };
+    let raw = serde_json::to_string(&cached).map_err(|err| AppError::json(&session, err))?;
+    file.write_all(raw.as_bytes())
+        .map_err(|err| AppError::io(&session, err))
+}
+
+/// Whether `path` is a real directory (not a symlink) that only its owner, the
+/// current user, can access.
+fn is_private(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_dir() && is_private_file(&meta))
+}
+
+#[cfg(unix)]
+fn is_private_file(meta: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    // SAFETY: getuid has no preconditions and cannot fail.
+    let uid = unsafe { libc::getuid() };
+    meta.uid() == uid && meta.mode() & 0o077 == 0
+}
+
+#[cfg(not(unix))]
+fn is_private_file(_meta: &fs::Metadata) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn sealed_tokens_open_only_with_the_right_passphrase() {
+        let tmp = TempDir::new().expect("tempdir");
+        let paths = AppPaths::under(tmp.path());
+
+        let mut unlocked = create(&paths, "correct horse").expect("create vault");
+        let first = unlocked.seal("token-a");
+        let second = unlocked.seal("token-a");
+        assert_ne!(first, second);
+        unlocked.save(&paths).expect("save vault");
+        let raw = fs::read_to_string(&paths.vault_path).expect("read vault");
+        assert!(!raw.contains("token-a"));
+
+        let reopened =
+            unlock_with(&paths, load(&paths).expect("load"), "correct horse").expect("unlock");
+        assert_eq!(
+            reopened.open(&paths, &first).expect("open"),
+            Some("token-a".to_owned())
+        );
+        assert_eq!(reopened.open(&paths, "missing").expect("open"), None);
+
+        assert!(matches!(
+            unlock_with(&paths, load(&paths).expect("load"), "wrong"),
+            Err(AppError::VaultPassphraseIncorrect)
+        ));
+    }
+
+    #[test]
+    fn cached_keys_expire_and_must_be_private() {
+        let tmp = TempDir::new().expect("tempdir");
+        let paths = AppPaths::under(tmp.path());
+        let session = session_key_path(&paths).expect("session path");
+        let key = [7u8; 32];
+
+        remember_key(&paths, &key).expect("remember key");
+        assert_eq!(cached_key(&paths), Some(key));
+        // Re-caching replaces the file instead of writing through it.
+        remember_key(&paths, &key).expect("remember key again");
+        assert_eq!(cached_key(&paths), Some(key));
+
+        let expired = SessionKey {
+            expires_at_ms: clock::now_millis() - 1,
+            key: STANDARD.encode(key),
+        };
+        fs::write(
+            &session,
+            serde_json::to_string(&expired).expect("serialize"),
+        )
+        .expect("write expired key");
+        assert_eq!(cached_key(&paths), None);
+        assert!(!session.exists());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            remember_key(&paths, &key).expect("remember key");
+            fs::set_permissions(&session, fs::Permissions::from_mode(0o644)).expect("chmod");
+            assert_eq!(cached_key(&paths), None);
+
+            let session_dir = paths.session_dir.as_ref().expect("session dir");
+            fs::set_permissions(session_dir, fs::Permissions::from_mode(0o755)).expect("chmod");
+            assert!(remember_key(&paths, &key).is_err());
+        }
+    }
+
+    #[test]
+    fn keys_are_not_cached_without_a_runtime_dir() {
+        let tmp = TempDir::new().expect("tempdir");
+        let paths = AppPaths {
+            session_dir: None,
+            ..AppPaths::under(tmp.path())
+        };
+
+        remember_key(&paths, &[7u8; 32]).expect("remember key");
+        assert_eq!(cached_key(&paths), None);
+        assert!(!lock(&paths).expect("lock"));
+        assert!(!tmp.path().join("runtime").exists());
+    }
+}
@@ -0,0 +1,12 @@
+# ccswitcher: apply the preset chosen by the nearest .ccswitcher file after `cd`.
+# Add to ~/.bashrc:  eval "$(ccswitcher hook bash)"
+_ccswitcher_auto() {
+  if [ "${_CCSWITCHER_LAST_PWD-}" != "$PWD" ]; then
+    _CCSWITCHER_LAST_PWD="$PWD"
+    command ccswitcher auto --quiet
+  fi
+}
+case ";${PROMPT_COMMAND-};" in
+  *";_ccswitcher_auto;"*) ;;
+  *) PROMPT_COMMAND="_ccswitcher_auto${PROMPT_COMMAND:+;$PROMPT_COMMAND}" ;;
+esac
@@ -0,0 +1,6 @@
+# ccswitcher: apply the preset chosen by the nearest .ccswitcher file after `cd`.
+# Add to ~/.config/fish/config.fish:  ccswitcher hook fish | source
+function __ccswitcher_auto --on-variable PWD
+    command ccswitcher auto --quiet
+end
+__ccswitcher_auto
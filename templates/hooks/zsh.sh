@@ -0,0 +1,8 @@
+# ccswitcher: apply the preset chosen by the nearest .ccswitcher file after `cd`.
+# Add to ~/.zshrc:  eval "$(ccswitcher hook zsh)"
+_ccswitcher_auto() {
+  command ccswitcher auto --quiet
+}
+autoload -Uz add-zsh-hook
+add-zsh-hook chpwd _ccswitcher_auto
+_ccswitcher_auto